@@ -1,50 +1,28 @@
-use advent_of_code_2019::*;
+use advent_of_code_2019::{day, day12};
 
 use criterion::{criterion_group, criterion_main, Criterion};
 
 pub fn criterion_benchmark(c: &mut Criterion) {
-    macro_rules! run {
-        ($l:expr) => {
-            c.bench_function(stringify!($l), |b| b.iter(|| $l));
-        }
+    for d in day::all() {
+        c.bench_function(&format!("{}::part1()", d.name()), |b| b.iter(|| d.part1()));
+        c.bench_function(&format!("{}::part2()", d.name()), |b| b.iter(|| d.part2()));
     }
-    run!(day01::part1());
-    run!(day01::part2());
-    run!(day02::part1());
-    run!(day02::part2());
-    run!(day03::part1());
-    run!(day03::part2());
-    run!(day04::part1());
-    run!(day04::part2());
-    run!(day05::part1());
-    run!(day05::part2());
-    run!(day06::part1());
-    run!(day06::part2());
-    run!(day07::part1());
-    run!(day07::part2());
-    run!(day08::part1());
-    run!(day08::part2());
-    run!(day09::part1());
-    run!(day09::part2());
-    run!(day10::part1());
-    run!(day10::part2());
-    run!(day11::part1());
-    run!(day11::part2());
-    run!(day12::part1());
-    run!(day12::part2());
-    run!(day13::part1());
-    run!(day13::part2());
-    run!(day14::part1());
-    run!(day14::part2());
-    run!(day15::part1());
-    run!(day15::part2());
-    run!(day16::part1());
-    run!(day16::part2());
-    run!(day17::part1());
-    run!(day17::part2());
-    run!(day18::part1());
-    run!(day18::part2());
 }
 
-criterion_group!(benches, criterion_benchmark);
+/// Compare day12's per-axis cycle detection one axis after another against one thread per axis
+///
+/// With a core per axis, the threaded version takes as long as the slowest axis instead of the sum
+/// of all three. For the puzzle input that's y, with 52% of the steps, so it should take roughly
+/// half as long. On a single core the threads only add overhead: ~400ms vs ~230ms sequentially.
+pub fn day12_axis_cycles_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("day12");
+    // Each iteration takes a few hundred milliseconds
+    group.sample_size(10);
+    let moons = day12::read_input("day12_input.txt");
+    group.bench_function("find_axis_cycles_sequential()", |b| b.iter(|| day12::find_axis_cycles_sequential(&moons)));
+    group.bench_function("find_axis_cycles_parallel()", |b| b.iter(|| day12::find_axis_cycles_parallel(&moons)));
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark, day12_axis_cycles_benchmark);
 criterion_main!(benches);
@@ -0,0 +1,27 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// With the `embedded` feature, generate a table of every file in `data/` for `include_str!`
+fn main() {
+    println!("cargo:rerun-if-changed=data");
+    if env::var_os("CARGO_FEATURE_EMBEDDED").is_none() {
+        return;
+    }
+    let data_dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("data");
+    let mut entries: Vec<_> = fs::read_dir(&data_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_file())
+        .collect();
+    entries.sort();
+    let out_path = Path::new(&env::var("OUT_DIR").unwrap()).join("embedded.rs");
+    let mut out = fs::File::create(out_path).unwrap();
+    writeln!(out, "&[").unwrap();
+    for path in entries {
+        let name = path.file_name().unwrap().to_str().unwrap();
+        writeln!(out, "    ({:?}, include_str!({:?})),", name, path.to_str().unwrap()).unwrap();
+    }
+    writeln!(out, "]").unwrap();
+}
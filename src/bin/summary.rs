@@ -0,0 +1,67 @@
+use std::process;
+use std::time::{Duration, Instant};
+
+use advent_of_code_2019::day;
+
+/// Width of the bar for the slowest part
+const BAR_WIDTH: usize = 40;
+
+/// Format `timings` as a table sorted from slowest to fastest, with a bar showing each duration
+/// relative to the slowest
+fn format_summary(timings: &[(String, Duration)]) -> Vec<String> {
+    let mut sorted: Vec<&(String, Duration)> = timings.iter().collect();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let name_width = sorted.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    let slowest = sorted.first().map(|(_, elapsed)| elapsed.as_nanos()).unwrap_or(0);
+    sorted
+        .iter()
+        .map(|(name, elapsed)| {
+            let bar = (elapsed.as_nanos() * BAR_WIDTH as u128).checked_div(slowest).unwrap_or(0) as usize;
+            format!("{:<width$} {:>12} {}", name, format!("{:?}", elapsed), "#".repeat(bar), width = name_width)
+        })
+        .collect()
+}
+
+fn main() {
+    let mut timings: Vec<(String, Duration)> = Vec::new();
+    let mut failed = false;
+    for (name, part) in day::select(&[]).unwrap() {
+        let start = Instant::now();
+        let result = part();
+        let elapsed = Instant::now().duration_since(start);
+        if let Err(e) = result {
+            eprintln!("{}: {}", name, e);
+            failed = true;
+        }
+        timings.push((name, elapsed));
+    }
+    for line in format_summary(&timings) {
+        println!("{}", line);
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_summary() {
+        let timings: Vec<(String, Duration)> = vec![
+            ("day01::part1()".to_string(), Duration::from_millis(10)),
+            ("day12::part2()".to_string(), Duration::from_millis(200)),
+            ("day07::part2()".to_string(), Duration::from_millis(50)),
+            ("day07::part1()".to_string(), Duration::from_millis(50)),
+        ];
+        assert_eq!(format_summary(&timings), vec![
+            format!("day12::part2()        200ms {}", "#".repeat(40)),
+            format!("day07::part1()         50ms {}", "#".repeat(10)),
+            format!("day07::part2()         50ms {}", "#".repeat(10)),
+            format!("day01::part1()         10ms {}", "#".repeat(2)),
+        ]);
+        assert!(format_summary(&[]).is_empty());
+        assert_eq!(format_summary(&[("x".to_string(), Duration::from_secs(0))]), vec!["x          0ns "]);
+    }
+}
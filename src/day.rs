@@ -0,0 +1,161 @@
+use crate::*;
+
+/// A day's puzzle solution, with each part's answer formatted for display
+pub trait Day {
+    /// Module name of the solution, e.g. `"day01"`
+    fn name(&self) -> &'static str;
+    fn part1(&self) -> error::Result<String>;
+    fn part2(&self) -> error::Result<String>;
+}
+
+/// Implement `Day` for a unit struct wrapping the `part1()`/`part2()` of a solution module
+///
+/// Solutions that return `error::Result` are marked with `?`, e.g. `day!(Day01, day01?)`.
+macro_rules! day {
+    ($t:ident, $m:ident) => {
+        struct $t;
+
+        impl Day for $t {
+            fn name(&self) -> &'static str { stringify!($m) }
+            fn part1(&self) -> error::Result<String> { Ok(format!("{}", $m::part1())) }
+            fn part2(&self) -> error::Result<String> { Ok(format!("{}", $m::part2())) }
+        }
+    };
+    ($t:ident, $m:ident?) => {
+        struct $t;
+
+        impl Day for $t {
+            fn name(&self) -> &'static str { stringify!($m) }
+            fn part1(&self) -> error::Result<String> { Ok(format!("{}", $m::part1()?)) }
+            fn part2(&self) -> error::Result<String> { Ok(format!("{}", $m::part2()?)) }
+        }
+    };
+}
+
+day!(Day01, day01?);
+day!(Day02, day02?);
+day!(Day03, day03?);
+day!(Day04, day04?);
+day!(Day05, day05?);
+day!(Day06, day06?);
+day!(Day07, day07);
+day!(Day08, day08?);
+day!(Day09, day09);
+day!(Day10, day10);
+day!(Day11, day11?);
+day!(Day12, day12);
+day!(Day13, day13?);
+day!(Day14, day14);
+day!(Day15, day15);
+day!(Day16, day16);
+day!(Day17, day17);
+day!(Day18, day18);
+
+/// Get every day's solution, in order, such that day `n` is at index `n - 1`
+pub fn all() -> Vec<Box<dyn Day>> {
+    vec![
+        Box::new(Day01),
+        Box::new(Day02),
+        Box::new(Day03),
+        Box::new(Day04),
+        Box::new(Day05),
+        Box::new(Day06),
+        Box::new(Day07),
+        Box::new(Day08),
+        Box::new(Day09),
+        Box::new(Day10),
+        Box::new(Day11),
+        Box::new(Day12),
+        Box::new(Day13),
+        Box::new(Day14),
+        Box::new(Day15),
+        Box::new(Day16),
+        Box::new(Day17),
+        Box::new(Day18),
+    ]
+}
+
+/// A named, runnable part of a day's solution, e.g. `"day07::part2()"`
+pub type Selection = (String, Box<dyn Fn() -> error::Result<String>>);
+
+/// Select the parts to run from command-line arguments `[DAY [PART]]`
+///
+/// No arguments selects every part of every day, `DAY` selects both parts of that day, and
+/// `DAY PART` selects a single part.
+pub fn select(args: &[&str]) -> Result<Vec<Selection>, String> {
+    let days = all();
+    let days: Vec<Box<dyn Day>> = match args.first() {
+        None => days,
+        Some(arg) => {
+            let n: usize = arg.parse().map_err(|_| format!("invalid day: {}", arg))?;
+            if n < 1 || n > days.len() {
+                return Err(format!("no solution for day {}", n));
+            }
+            days.into_iter().skip(n - 1).take(1).collect()
+        },
+    };
+    let parts: Vec<u8> = match args.get(1) {
+        None => vec![1, 2],
+        Some(&"1") => vec![1],
+        Some(&"2") => vec![2],
+        Some(arg) => return Err(format!("invalid part: {}", arg)),
+    };
+    if args.len() > 2 {
+        return Err("too many arguments".to_string());
+    }
+    let mut selected: Vec<Selection> = Vec::new();
+    for d in days {
+        let d: std::rc::Rc<dyn Day> = d.into();
+        for &part in parts.iter() {
+            let d = d.clone();
+            let name = format!("{}::part{}()", d.name(), part);
+            if part == 1 {
+                selected.push((name, Box::new(move || d.part1())));
+            } else {
+                selected.push((name, Box::new(move || d.part2())));
+            }
+        }
+    }
+    Ok(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all() {
+        let days = all();
+        assert!(days.len() >= 16);
+        for (i, day) in days.iter().enumerate() {
+            assert_eq!(day.name(), format!("day{:02}", i + 1));
+        }
+    }
+
+    #[test]
+    fn test_day01() {
+        let day = &all()[0];
+        assert_eq!(day.part1().unwrap(), day01::part1().unwrap().to_string());
+        assert_eq!(day.part2().unwrap(), day01::part2().unwrap().to_string());
+    }
+
+    #[test]
+    fn test_select() {
+        assert_eq!(select(&[]).unwrap().len(), all().len() * 2);
+        let names: Vec<String> = select(&["07"]).unwrap().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["day07::part1()", "day07::part2()"]);
+        let selected = select(&["07", "2"]).unwrap();
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].0, "day07::part2()");
+        assert_eq!((selected[0].1)().unwrap(), day07::part2().to_string());
+    }
+
+    #[test]
+    fn test_select_invalid() {
+        assert!(select(&["x"]).is_err());
+        assert!(select(&["0"]).is_err());
+        assert!(select(&["99"]).is_err());
+        assert!(select(&["07", "3"]).is_err());
+        assert!(select(&["07", "1", "1"]).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use std::cmp::max;
 
+use crate::error::Result;
 use crate::util;
 
 fn calc_fuel(mass: &i32) -> i32 {
@@ -16,14 +17,28 @@ fn calc_fuel_recursive(mass: &i32) -> i32 {
     return total;
 }
 
-pub fn part1() -> i32 {
-    let data: Vec<i32> = util::read_data("day01_input.txt");
-    data.iter().map(calc_fuel).sum()
+/// Total fuel for every module's mass, optionally including the fuel for the fuel itself
+pub fn fuel_for_masses(masses: impl IntoIterator<Item=i32>, recursive: bool) -> i32 {
+    let fuel = if recursive { calc_fuel_recursive } else { calc_fuel };
+    masses.into_iter().map(|mass| fuel(&mass)).sum()
 }
 
-pub fn part2() -> i32 {
-    let data: Vec<i32> = util::read_data("day01_input.txt");
-    data.iter().map(calc_fuel_recursive).sum()
+pub fn solve_part1(filename: &str) -> Result<i32> {
+    let data: Vec<i32> = util::read_data(filename)?;
+    Ok(fuel_for_masses(data, false))
+}
+
+pub fn part1() -> Result<i32> {
+    solve_part1("day01_input.txt")
+}
+
+pub fn solve_part2(filename: &str) -> Result<i32> {
+    let data: Vec<i32> = util::read_data(filename)?;
+    Ok(fuel_for_masses(data, true))
+}
+
+pub fn part2() -> Result<i32> {
+    solve_part2("day01_input.txt")
 }
 
 #[cfg(test)]
@@ -38,9 +53,24 @@ mod tests {
         assert_eq!(calc_fuel(&100756), 33583);
     }
 
+    #[test]
+    fn test_fuel_for_masses() {
+        let masses = || [12, 14, 1969, 100756].iter().cloned();
+        assert_eq!(fuel_for_masses(masses(), false), 2 + 2 + 654 + 33583);
+        assert_eq!(fuel_for_masses(masses(), true), 2 + 2 + 966 + 50346);
+        assert_eq!(fuel_for_masses((1..=3).map(|x| x * 1969), false), 654 + 1310 + 1967);
+        assert_eq!(fuel_for_masses(Vec::new(), true), 0);
+    }
+
+    #[test]
+    fn test_solve_example1() {
+        assert_eq!(solve_part1("day01_example1.txt").unwrap(), 2 + 2 + 654 + 33583);
+        assert_eq!(solve_part2("day01_example1.txt").unwrap(), 2 + 2 + 966 + 50346);
+    }
+
     #[test]
     fn test_part1() {
-        assert_eq!(part1(), 3224048);
+        assert_eq!(part1().unwrap(), 3224048);
     }
 
     #[test]
@@ -52,6 +82,6 @@ mod tests {
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(), 4833211);
+        assert_eq!(part2().unwrap(), 4833211);
     }
 }
@@ -1,42 +1,62 @@
+use crate::error::{AocError, Result};
 use crate::intcode;
 
-pub fn part1() -> intcode::Word {
-    let mut emulator = intcode::Emulator::from_data_file("day02_input.txt");
-    emulator.set(1, 12);
-    emulator.set(2, 2);
+/// Run a copy of `base` with `noun` and `verb` patched into addresses 1 and 2, returning the
+/// resulting value at address 0
+pub fn run_noun_verb(base: &intcode::Emulator, noun: intcode::Word, verb: intcode::Word) -> intcode::Word {
+    let mut emulator = base.clone();
+    emulator.patch(&[(1, noun), (2, verb)]);
     emulator.run();
     emulator.get(0)
 }
 
-pub fn part2() -> intcode::Word {
-    let base = intcode::Emulator::from_data_file("day02_input.txt");
+pub fn solve_part1(filename: &str) -> Result<intcode::Word> {
+    let base = intcode::Emulator::from_data_file(filename)?;
+    Ok(run_noun_verb(&base, 12, 2))
+}
+
+pub fn part1() -> Result<intcode::Word> {
+    solve_part1("day02_input.txt")
+}
+
+pub fn solve_part2(filename: &str) -> Result<intcode::Word> {
+    let base = intcode::Emulator::from_data_file(filename)?;
     let target = 19690720 as intcode::Word;
 
-    'outer: for x in 0..=99 {
+    for x in 0..=99 {
         for y in 0..=99 {
-            let mut emulator = base.clone();
-            emulator.set(1, x);
-            emulator.set(2, y);
-            emulator.run();
-            if emulator.get(0) == target {
-                return 100 * x + y;
+            if run_noun_verb(&base, x, y) == target {
+                return Ok(100 * x + y);
             }
         }
     }
-    panic!("didn't find a solution");
+    Err(AocError::NoSolution("no noun/verb produces the target output".to_string()))
+}
+
+pub fn part2() -> Result<intcode::Word> {
+    solve_part2("day02_input.txt")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_run_noun_verb() {
+        let base = intcode::Emulator::from_data_file("day02_input.txt").unwrap();
+        assert_eq!(run_noun_verb(&base, 12, 2), 3562672);
+        assert_eq!(run_noun_verb(&base, 82, 50), 19690720);
+        // The base emulator is left untouched
+        assert_eq!(base.get(1), intcode::Emulator::from_data_file("day02_input.txt").unwrap().get(1));
+    }
+
     #[test]
     fn test_part1() {
-        assert_eq!(part1(), 3562672);
+        assert_eq!(part1().unwrap(), 3562672);
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(), 8250);
+        assert_eq!(part2().unwrap(), 8250);
     }
 }
@@ -1,7 +1,7 @@
 use std::collections::HashSet;
-use std::num::ParseIntError;
 use std::str::FromStr;
 
+use crate::error::{AocError, Result};
 use crate::util;
 use crate::util::*;
 
@@ -19,20 +19,20 @@ struct Wire {
 }
 
 impl FromStr for Wire {
-    type Err = ParseIntError;
+    type Err = AocError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let vectors: Vec<Vector2D> = s
             .split(",")
             .map(|x| x.split_at(1))
-            .map(|(d, v)| match (d, v.parse::<i32>().unwrap()) {
-                ("L", v) => vector!(-v, 0),
-                ("R", v) => vector!(v, 0),
-                ("U", v) => vector!(0, v),
-                ("D", v) => vector!(0, -v),
-                _ => panic!("unrecognised direction"),
+            .map(|(d, v)| match (d, v.parse::<i32>()?) {
+                ("L", v) => Ok(vector!(-v, 0)),
+                ("R", v) => Ok(vector!(v, 0)),
+                ("U", v) => Ok(vector!(0, v)),
+                ("D", v) => Ok(vector!(0, -v)),
+                (d, _) => Err(AocError::Parse(format!("unrecognised direction: {}", d))),
             })
-            .collect();
+            .collect::<Result<_>>()?;
         let mut points: Vec<Point2D> = vec![point!(0, 0)];
         points.extend(
             vectors
@@ -54,83 +54,114 @@ impl FromStr for Wire {
     }
 }
 
-/// Find the intersection of two lines
-///
-/// Assuming that `l1` and `l2` are both axis-aligned, returns `Some(Point2D)` if the lines
-/// intersect or `None` if they do not.
-fn axis_aligned_line_intersection(l1: &Line2D, l2: &Line2D) -> Option<Point2D> {
-    let a1 = l1.axis().unwrap();
-    let a2 = l2.axis().unwrap();
-    if a1 == a2 {
-        // Parallel lines never intersect!
-        return None;
-    };
-    // Ensure the "first" line is always horizontal and the "second" line is always vertical
-    let (l1, l2) = if a1 == Axis::Horizontal { (l1, l2) } else { (l2, l1) };
-    // Get bounding boxes to make sure we're dealing with ordered values for range checks
-    let (l1_min, l1_max) = l1.bounding_box();
-    let (l2_min, l2_max) = l2.bounding_box();
-    // Vertical line's X coordinate within horizontal line's range
-    let horizontal_overlap = (l1_min.x ..= l1_max.x).contains(&l2_max.x);
-    // Horizontal line's Y coordinate within vertical line's range
-    let vertical_overlap = (l2_min.y ..= l2_max.y).contains(&l1_min.y);
-    if horizontal_overlap && vertical_overlap {
-        // Intersection must be at X coordinate of vertical line and Y coordinate of horizontal line
-        return Some(point!(l2_min.x, l1_min.y));
-    } else {
-        return None;
+/// Axis-aligned lines grouped by orientation and sorted by their fixed coordinate, so the lines
+/// that might cross a perpendicular line can be found with a binary search
+struct LineIndex<'a, T> {
+    /// Horizontal lines, keyed by Y coordinate
+    horizontal: Vec<(i32, &'a Line2D, T)>,
+    /// Vertical lines, keyed by X coordinate
+    vertical: Vec<(i32, &'a Line2D, T)>,
+}
+
+impl<'a, T> LineIndex<'a, T> {
+    fn new(lines: impl IntoIterator<Item=(&'a Line2D, T)>) -> LineIndex<'a, T> {
+        let mut index = LineIndex { horizontal: Vec::new(), vertical: Vec::new() };
+        for (line, data) in lines {
+            // Only allow axis-aligned lines, will panic if not
+            match line.axis().unwrap() {
+                Axis::Horizontal => index.horizontal.push((line.start.y, line, data)),
+                Axis::Vertical => index.vertical.push((line.start.x, line, data)),
+            }
+        }
+        index.horizontal.sort_by_key(|x| x.0);
+        index.vertical.sort_by_key(|x| x.0);
+        index
+    }
+
+    /// Get the perpendicular lines whose fixed coordinate is within the extent of `line`
+    ///
+    /// Parallel lines never intersect, so these are the only lines that might cross `line`.
+    fn candidates(&self, line: &Line2D) -> &[(i32, &'a Line2D, T)] {
+        let (line_min, line_max) = line.bounding_box();
+        let (lines, low, high) = match line.axis().unwrap() {
+            Axis::Horizontal => (&self.vertical, line_min.x, line_max.x),
+            Axis::Vertical => (&self.horizontal, line_min.y, line_max.y),
+        };
+        let start = lines.partition_point(|x| x.0 < low);
+        let end = lines.partition_point(|x| x.0 <= high);
+        &lines[start .. end]
     }
 }
 
+/// Find the points where perpendicular lines in `a` and `b` intersect according to `f`
 fn find_intersections(a: &[Line2D], b: &[Line2D], f: impl Fn(&Line2D, &Line2D) -> Option<Point2D>) -> HashSet<Point2D> {
-    iproduct!(a, b)
+    let index = LineIndex::new(b.iter().map(|l| (l, ())));
+    a.iter()
+        .flat_map(|a| index.candidates(a).iter().map(move |(_, b, _)| (a, *b)))
         .filter_map(|(a, b)| f(a, b))
         .collect()
 }
 
 fn find_intersections_with_costs(a: &[Line2D], b: &[Line2D]) -> Vec<Intersection> {
-    let a_with_costs: Vec<(&Line2D, i32)> =
+    find_intersections_with_segments(a, b).into_iter().map(|(i, _, _)| i).collect()
+}
+
+/// Like `find_intersections_with_costs()`, but also returns the indices of the crossing lines in
+/// `a` and `b` respectively
+fn find_intersections_with_segments(a: &[Line2D], b: &[Line2D]) -> Vec<(Intersection, usize, usize)> {
+    let a_with_costs: Vec<(usize, &Line2D, i32)> =
         a.iter()
-            .scan(0, |state, l| {
+            .enumerate()
+            .scan(0, |state, (i, l)| {
                 let old_state = *state;
                 *state += l.manhattan_length();
-                Some((l, old_state))
+                Some((i, l, old_state))
             })
             .collect();
-    let b_with_costs: Vec<(&Line2D, i32)> =
+    let b_with_costs: Vec<(usize, &Line2D, i32)> =
         b.iter()
-            .scan(0, |state, l| {
+            .enumerate()
+            .scan(0, |state, (i, l)| {
                 let old_state = *state;
                 *state += l.manhattan_length();
-                Some((l, old_state))
+                Some((i, l, old_state))
             })
             .collect();
-    iproduct!(a_with_costs, b_with_costs)
-        .filter_map(|((a_line, a_base), (b_line, b_base))| {
-            a_line.intersection_with(b_line).and_then(|Intersection(p, a_cost, b_cost)| {
-                Some(Intersection(p, a_base + a_cost, b_base + b_cost))
+    let b_index = LineIndex::new(b_with_costs.into_iter().map(|(i, l, base)| (l, (i, base))));
+    a_with_costs
+        .into_iter()
+        .flat_map(|a| b_index.candidates(a.1).iter().map(move |&(_, b_line, b)| (a, b_line, b)))
+        .filter_map(|((a_index, a_line, a_base), b_line, (b_index, b_base))| {
+            a_line.intersection_with(b_line).map(|Intersection(p, a_cost, b_cost)| {
+                (Intersection(p, a_base + a_cost, b_base + b_cost), a_index, b_index)
             })
         })
         .collect()
 }
 
-pub fn part1() -> i32 {
-    let wires: Vec<Wire> = util::read_data("day03_input.txt");
+pub fn solve_part1(filename: &str) -> Result<i32> {
+    let wires: Vec<Wire> = util::read_data(filename)?;
     let wire1 = &wires[0];
     let wire2 = &wires[1];
-    let intersections = find_intersections(&wire1.lines, &wire2.lines, axis_aligned_line_intersection);
+    let intersections = find_intersections(&wire1.lines, &wire2.lines, |a, b| {
+        a.intersection_with(b).map(|Intersection(p, _, _)| p)
+    });
     let mut distances: Vec<i32> =
         intersections
         .iter()
-        .map(|&x| (x - point!(0, 0)).manhattan_length())
+        .map(|x| x.manhattan_distance(&point!(0, 0)))
         .filter(|&x| x > 0)
         .collect();
     distances.sort();
-    *distances.first().unwrap()
+    distances.first().cloned().ok_or_else(|| AocError::NoSolution("wires don't intersect".to_string()))
+}
+
+pub fn part1() -> Result<i32> {
+    solve_part1("day03_input.txt")
 }
 
-pub fn part2() -> i32 {
-    let wires: Vec<Wire> = util::read_data("day03_input.txt");
+pub fn solve_part2(filename: &str) -> Result<i32> {
+    let wires: Vec<Wire> = util::read_data(filename)?;
     let wire1 = &wires[0];
     let wire2 = &wires[1];
     let intersections = find_intersections_with_costs(&wire1.lines, &wire2.lines);
@@ -141,12 +172,22 @@ pub fn part2() -> i32 {
         .filter(|&x| x > 0)
         .collect();
     distances.sort();
-    *distances.first().unwrap()
+    distances.first().cloned().ok_or_else(|| AocError::NoSolution("wires don't intersect".to_string()))
+}
+
+pub fn part2() -> Result<i32> {
+    solve_part2("day03_input.txt")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use itertools::iproduct;
+
+    /// Get just the point where `l1` and `l2` intersect, if they do
+    fn intersection_point(l1: &Line2D, l2: &Line2D) -> Option<Point2D> {
+        l1.intersection_with(l2).map(|Intersection(p, _, _)| p)
+    }
 
     #[test]
     fn test_wire_parser() {
@@ -174,63 +215,130 @@ mod tests {
     #[test]
     fn test_line_intersection() {
         // Nice simple horizontal + vertical lines that have the same end
-        assert_eq!(Some(point!(5, 5)), axis_aligned_line_intersection(
+        assert_eq!(Some(point!(5, 5)), intersection_point(
             &Line2D{start: point!(0, 5), end: point!(5, 5)},
             &Line2D{start: point!(5, 0), end: point!(5, 5)},
         ));
         // Arguments reversed
-        assert_eq!(Some(point!(5, 5)), axis_aligned_line_intersection(
+        assert_eq!(Some(point!(5, 5)), intersection_point(
             &Line2D{start: point!(5, 0), end: point!(5, 5)},
             &Line2D{start: point!(0, 5), end: point!(5, 5)},
         ));
         // Line "directions" reversed
-        assert_eq!(Some(point!(5, 5)), axis_aligned_line_intersection(
+        assert_eq!(Some(point!(5, 5)), intersection_point(
             &Line2D{start: point!(5, 5), end: point!(5, 0)},
             &Line2D{start: point!(5, 5), end: point!(0, 5)},
         ));
         // Lines with same start
-        assert_eq!(Some(point!(0, 0)), axis_aligned_line_intersection(
+        assert_eq!(Some(point!(0, 0)), intersection_point(
             &Line2D{start: point!(0, 0), end: point!(5, 0)},
             &Line2D{start: point!(0, 0), end: point!(0, 5)},
         ));
         // First line ends on second line
-        assert_eq!(Some(point!(5, 5)), axis_aligned_line_intersection(
+        assert_eq!(Some(point!(5, 5)), intersection_point(
             &Line2D{start: point!(0, 5), end: point!(5, 5)},
             &Line2D{start: point!(5, 0), end: point!(5, 10)},
         ));
         // Second line ends on first line
-        assert_eq!(Some(point!(5, 5)), axis_aligned_line_intersection(
+        assert_eq!(Some(point!(5, 5)), intersection_point(
             &Line2D{start: point!(5, 0), end: point!(5, 10)},
             &Line2D{start: point!(0, 5), end: point!(5, 5)},
         ));
         // Lines intersect somewhere that's not a line end
-        assert_eq!(Some(point!(5, 3)), axis_aligned_line_intersection(
+        assert_eq!(Some(point!(5, 3)), intersection_point(
             &Line2D{start: point!(5, 0), end: point!(5, 10)},
             &Line2D{start: point!(3, 3), end: point!(20, 3)},
         ));
         // Lines don't intersect at all
-        assert_eq!(None, axis_aligned_line_intersection(
+        assert_eq!(None, intersection_point(
             &Line2D{start: point!(5, 0), end: point!(5, 10)},
             &Line2D{start: point!(0, 5), end: point!(3, 5)},
         ));
+        // Parallel lines never intersect, even if they overlap
+        assert_eq!(None, intersection_point(
+            &Line2D{start: point!(0, 5), end: point!(5, 5)},
+            &Line2D{start: point!(3, 5), end: point!(8, 5)},
+        ));
+        // Costs are the distance along each line from its start
+        assert_eq!(Some(Intersection(point!(5, 3), 3, 2)), Line2D{start: point!(5, 0), end: point!(5, 10)}
+            .intersection_with(&Line2D{start: point!(3, 3), end: point!(20, 3)}));
+        assert_eq!(Some(Intersection(point!(5, 3), 15, 7)), Line2D{start: point!(20, 3), end: point!(3, 3)}
+            .intersection_with(&Line2D{start: point!(5, 10), end: point!(5, 0)}));
+    }
+
+    /// Find intersections by testing every line in `a` against every line in `b`
+    fn brute_force_intersections(a: &[Line2D], b: &[Line2D]) -> HashSet<Point2D> {
+        iproduct!(a, b)
+            .filter_map(|(a, b)| intersection_point(a, b))
+            .collect()
+    }
+
+    /// Find intersections with costs by testing every line in `a` against every line in `b`
+    fn brute_force_intersections_with_segments(a: &[Line2D], b: &[Line2D]) -> HashSet<(Intersection, usize, usize)> {
+        let costs = |lines: &[Line2D]| -> Vec<i32> {
+            lines.iter().scan(0, |state, l| {
+                let old_state = *state;
+                *state += l.manhattan_length();
+                Some(old_state)
+            }).collect()
+        };
+        let (a_costs, b_costs) = (costs(a), costs(b));
+        iproduct!(0 .. a.len(), 0 .. b.len())
+            .filter_map(|(i, j)| {
+                a[i].intersection_with(&b[j]).map(|Intersection(p, a_cost, b_cost)| {
+                    (Intersection(p, a_costs[i] + a_cost, b_costs[j] + b_cost), i, j)
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_line_index_matches_brute_force() {
+        for filename in ["day03_example1.txt", "day03_example2.txt"].iter() {
+            let wires: Vec<Wire> = util::read_data(filename).unwrap();
+            let (a, b) = (&wires[0].lines, &wires[1].lines);
+            assert_eq!(find_intersections(a, b, intersection_point), brute_force_intersections(a, b));
+            let fast: HashSet<_> = find_intersections_with_segments(a, b).into_iter().collect();
+            assert_eq!(fast, brute_force_intersections_with_segments(a, b));
+        }
     }
 
     #[test]
     fn test_line_intersections() {
         let wire1 = "R8,U5,L5,D3".parse::<Wire>().unwrap();
         let wire2 = "U7,R6,D4,L4".parse::<Wire>().unwrap();
-        let intersections = find_intersections(&wire1.lines, &wire2.lines, axis_aligned_line_intersection);
+        let intersections = find_intersections(&wire1.lines, &wire2.lines, intersection_point);
         let expected = [point!(0, 0), point!(3, 3), point!(6, 5)].iter().cloned().collect();
         assert_eq!(intersections, expected);
     }
 
+    #[test]
+    fn test_intersection_segments() {
+        let wire1 = "R8,U5,L5,D3".parse::<Wire>().unwrap();
+        let wire2 = "U7,R6,D4,L4".parse::<Wire>().unwrap();
+        let intersections = find_intersections_with_segments(&wire1.lines, &wire2.lines);
+        assert!(intersections.contains(&(Intersection(point!(6, 5), 15, 15), 2, 2)));
+        assert!(intersections.contains(&(Intersection(point!(3, 3), 20, 20), 3, 3)));
+        for (Intersection(p, _, _), a, b) in intersections.iter() {
+            assert_eq!(wire1.lines[*a].intersection_with(&wire2.lines[*b]).map(|i| i.0), Some(*p));
+        }
+    }
+
+    #[test]
+    fn test_solve_examples() {
+        assert_eq!(solve_part1("day03_example1.txt").unwrap(), 6);
+        assert_eq!(solve_part2("day03_example1.txt").unwrap(), 30);
+        assert_eq!(solve_part1("day03_example2.txt").unwrap(), 159);
+        assert_eq!(solve_part2("day03_example2.txt").unwrap(), 610);
+    }
+
     #[test]
     fn test_part1() {
-        assert_eq!(part1(), 860);
+        assert_eq!(part1().unwrap(), 860);
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(), 9238);
+        assert_eq!(part2().unwrap(), 9238);
     }
 }
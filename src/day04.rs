@@ -1,12 +1,14 @@
+use std::cmp::max;
 use std::str::FromStr;
-use std::num::ParseIntError;
 use itertools::Itertools;
+use crate::error::{AocError, Result};
+use crate::util;
 
-trait Matcher {
+pub trait Matcher {
     fn apply(&self, digits: &[u8]) -> bool;
 }
 
-struct FunctionMatcher(fn(&[u8]) -> bool);
+pub struct FunctionMatcher(pub fn(&[u8]) -> bool);
 
 impl Matcher for FunctionMatcher {
     fn apply(&self, digits: &[u8]) -> bool {
@@ -14,7 +16,7 @@ impl Matcher for FunctionMatcher {
     }
 }
 
-struct AndMatcher<'a>(&'a[&'a dyn Matcher]);
+pub struct AndMatcher<'a>(pub &'a[&'a dyn Matcher]);
 
 impl<'a> Matcher for AndMatcher<'a> {
     fn apply(&self, digits: &[u8]) -> bool {
@@ -22,7 +24,7 @@ impl<'a> Matcher for AndMatcher<'a> {
     }
 }
 
-fn never_decreasing(digits: &[u8]) -> bool {
+pub fn never_decreasing(digits: &[u8]) -> bool {
     for (a, b) in digits.iter().tuple_windows() {
         if b < a {
             return false;
@@ -31,7 +33,7 @@ fn never_decreasing(digits: &[u8]) -> bool {
     return true;
 }
 
-fn has_double(digits: &[u8]) -> bool {
+pub fn has_double(digits: &[u8]) -> bool {
     for (a, b) in digits.iter().tuple_windows() {
         if a == b {
             return true;
@@ -40,7 +42,7 @@ fn has_double(digits: &[u8]) -> bool {
     return false;
 }
 
-fn has_isolated_double(digits: &[u8]) -> bool {
+pub fn has_isolated_double(digits: &[u8]) -> bool {
     for i in 0 .. digits.len() - 1 {
         let valid =
             digits[i] == digits[i + 1]
@@ -55,36 +57,25 @@ fn has_isolated_double(digits: &[u8]) -> bool {
 
 #[derive(Clone,Debug)]
 struct PasswordIterator {
-    start: [u8; 6],
-    end: [u8; 6],
-    current: [u8; 6],
+    start: Vec<u8>,
+    end: Vec<u8>,
+    current: Vec<u8>,
     done: bool,
 }
 
 impl PasswordIterator {
-    fn new(start: u32, end: u32) -> PasswordIterator {
+    /// Iterate over `start ..= end`, where each value is represented by `digits` digits
+    fn new(start: u32, end: u32, digits: usize) -> PasswordIterator {
         assert!(start <= end);
-        let mut out = PasswordIterator {
-            start: Default::default(),
-            end: Default::default(),
-            current: Default::default(),
-            done: false,
-        };
-        Self::create_digit_array(start, &mut out.start);
-        Self::create_digit_array(end, &mut out.end);
-        out.current = out.start;
-        out
-    }
-
-    fn create_digit_array(x: u32, dest: &mut [u8; 6]) {
-        for (i, n) in (0 .. dest.len()).rev().enumerate() {
-            dest[i] = (x / (10_u32.pow(n as u32)) % 10) as u8;
-        }
+        let start = util::to_digits(start as u64, digits);
+        let end = util::to_digits(end as u64, digits);
+        assert_eq!(end.len(), digits);
+        PasswordIterator { current: start.clone(), start, end, done: false }
     }
 }
 
 impl Iterator for PasswordIterator {
-    type Item = [u8; 6];
+    type Item = Vec<u8>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.done {
@@ -93,9 +84,9 @@ impl Iterator for PasswordIterator {
         if self.current == self.end {
             self.done = true;
         }
-        let out = self.current;
+        let out = self.current.clone();
         let mut increment: u8 = 1;
-        let mut index: usize = 6;
+        let mut index: usize = self.current.len();
         while increment > 0 && index > 0 {
             index -= 1;
             self.current[index] += increment;
@@ -110,34 +101,124 @@ impl Iterator for PasswordIterator {
     }
 }
 
+/// Parse a `start-end` range, using as many digits as the longer of the two as written
+fn parse_range(s: &str) -> Result<(u32, u32, usize)> {
+    let index = s.find("-").ok_or_else(|| AocError::Parse(format!("invalid range: {}", s)))?;
+    let start: u32 = s[0 .. index].parse()?;
+    let end: u32 = s[index+1 ..].parse()?;
+    let digits = max(index, s.len() - index - 1);
+    if start > end {
+        return Err(AocError::Parse(format!("range start is after end: {}", s)));
+    }
+    if util::to_digits(end as u64, digits).len() != digits {
+        return Err(AocError::Parse(format!("range end has more than {} digits: {}", digits, s)));
+    }
+    Ok((start, end, digits))
+}
+
 impl FromStr for PasswordIterator {
-    type Err = ParseIntError;
+    type Err = AocError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let index = s.find("-").unwrap();
-        let start: u32 = s[0 .. index].parse()?;
-        let end: u32 = s[index+1 ..].parse()?;
-        Ok(PasswordIterator::new(start, end))
+        let (start, end, digits) = parse_range(s)?;
+        Ok(PasswordIterator::new(start, end, digits))
+    }
+}
+
+/// Like `PasswordIterator`, but only generates values whose digits never decrease
+#[derive(Clone,Debug)]
+struct MonotonicPasswordIterator {
+    end: Vec<u8>,
+    current: Vec<u8>,
+    done: bool,
+}
+
+impl MonotonicPasswordIterator {
+    /// Iterate over never-decreasing values in `start ..= end`, represented by `digits` digits
+    fn new(start: u32, end: u32, digits: usize) -> MonotonicPasswordIterator {
+        let range = PasswordIterator::new(start, end, digits);
+        let mut current = range.start;
+        // The first never-decreasing value is found by repeating the digit before the first decrease
+        if let Some(i) = (1 .. current.len()).find(|&i| current[i] < current[i - 1]) {
+            let d = current[i - 1];
+            current[i ..].iter_mut().for_each(|x| *x = d);
+        }
+        let done = current > range.end;
+        MonotonicPasswordIterator { end: range.end, current, done }
     }
 }
 
+impl Iterator for MonotonicPasswordIterator {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let out = self.current.clone();
+        // Increment the last digit that isn't a 9, and the digits after it can't be any smaller
+        match self.current.iter().rposition(|&x| x < 9) {
+            Some(i) => {
+                let d = self.current[i] + 1;
+                self.current[i ..].iter_mut().for_each(|x| *x = d);
+                self.done = self.current > self.end;
+            },
+            None => self.done = true,
+        }
+        Some(out)
+    }
+}
+
+impl FromStr for MonotonicPasswordIterator {
+    type Err = AocError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end, digits) = parse_range(s)?;
+        Ok(MonotonicPasswordIterator::new(start, end, digits))
+    }
+}
+
+/// Count the passwords in `range` (e.g. `"152085-670283"`) that satisfy `matcher`
+pub fn count_matching(range: &str, matcher: &dyn Matcher) -> Result<usize> {
+    let iterator = range.parse::<PasswordIterator>()?;
+    Ok(iterator.filter(|x| matcher.apply(x)).count())
+}
+
+/// Like `count_matching`, but only never-decreasing candidates are generated
+///
+/// Only equivalent to `count_matching` when `matcher` also requires `never_decreasing`.
+pub fn count_matching_monotonic(range: &str, matcher: &dyn Matcher) -> Result<usize> {
+    let iterator = range.parse::<MonotonicPasswordIterator>()?;
+    Ok(iterator.filter(|x| matcher.apply(x)).count())
+}
+
+/// Read the puzzle's password range from the first line of `filename`
+fn read_range(filename: &str) -> Result<String> {
+    util::read_one(filename)
+}
 
-pub fn part1() -> usize {
+pub fn solve_part1(filename: &str) -> Result<usize> {
     let matcher = AndMatcher(&[
         &FunctionMatcher(never_decreasing),
         &FunctionMatcher(has_double),
     ]);
-    let iterator = "152085-670283".parse::<PasswordIterator>().unwrap();
-    iterator.filter(|x| matcher.apply(x)).count()
+    count_matching_monotonic(&read_range(filename)?, &matcher)
+}
+
+pub fn part1() -> Result<usize> {
+    solve_part1("day04_input.txt")
 }
 
-pub fn part2() -> usize {
+pub fn solve_part2(filename: &str) -> Result<usize> {
     let matcher = AndMatcher(&[
         &FunctionMatcher(never_decreasing),
         &FunctionMatcher(has_isolated_double),
     ]);
-    let iterator = "152085-670283".parse::<PasswordIterator>().unwrap();
-    iterator.filter(|x| matcher.apply(x)).count()
+    count_matching_monotonic(&read_range(filename)?, &matcher)
+}
+
+pub fn part2() -> Result<usize> {
+    solve_part2("day04_input.txt")
 }
 
 #[cfg(test)]
@@ -146,37 +227,142 @@ mod tests {
 
     #[test]
     fn test_password_iterator_to_digits() {
-        let mut dest = [0_u8; 6];
-        PasswordIterator::create_digit_array(123456, &mut dest);
-        assert_eq!(dest, [1, 2, 3, 4, 5, 6]);
-        PasswordIterator::create_digit_array(123, &mut dest);
-        assert_eq!(dest, [0, 0, 0, 1, 2, 3]);
+        let iterator = PasswordIterator::new(123, 123456, 6);
+        assert_eq!(iterator.end, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(iterator.start, [0, 0, 0, 1, 2, 3]);
     }
 
     #[test]
     fn test_password_iterator() {
-        let mut pi = "1234-5678".parse::<PasswordIterator>().unwrap();
+        let mut pi = "001234-005678".parse::<PasswordIterator>().unwrap();
         assert_eq!(pi.start, [0, 0, 1, 2, 3, 4]);
         assert_eq!(pi.end, [0, 0, 5, 6, 7, 8]);
         assert_eq!(pi.current, [0, 0, 1, 2, 3, 4]);
-        assert_eq!(pi.next(), Some([0, 0, 1, 2, 3, 4]));
+        assert_eq!(pi.next(), Some(vec![0, 0, 1, 2, 3, 4]));
         assert_eq!(pi.start, [0, 0, 1, 2, 3, 4]);
         assert_eq!(pi.end, [0, 0, 5, 6, 7, 8]);
         assert_eq!(pi.current, [0, 0, 1, 2, 3, 5]);
-        pi.current = [0, 0, 5, 6, 7, 7];
-        assert_eq!(pi.next(), Some([0, 0, 5, 6, 7, 7]));
-        assert_eq!(pi.next(), Some([0, 0, 5, 6, 7, 8]));
+        pi.current = vec![0, 0, 5, 6, 7, 7];
+        assert_eq!(pi.next(), Some(vec![0, 0, 5, 6, 7, 7]));
+        assert_eq!(pi.next(), Some(vec![0, 0, 5, 6, 7, 8]));
         assert_eq!(pi.next(), None);
         assert_eq!(pi.next(), None);
     }
 
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("152085-670283").unwrap(), (152085, 670283, 6));
+        assert_eq!(parse_range("5-5").unwrap(), (5, 5, 1));
+        assert!(matches!(parse_range("5-3"), Err(AocError::Parse(_))));
+        assert!(matches!("5-3".parse::<PasswordIterator>(), Err(AocError::Parse(_))));
+        assert!(matches!("5-3".parse::<MonotonicPasswordIterator>(), Err(AocError::Parse(_))));
+    }
+
+    #[test]
+    fn test_password_iterator_variable_length() {
+        let pi = "997-1001".parse::<PasswordIterator>().unwrap();
+        assert_eq!(pi.collect::<Vec<_>>(), vec![
+            vec![0, 9, 9, 7],
+            vec![0, 9, 9, 8],
+            vec![0, 9, 9, 9],
+            vec![1, 0, 0, 0],
+            vec![1, 0, 0, 1],
+        ]);
+        let pi = PasswordIterator::new(98, 101, 3);
+        assert_eq!(pi.collect::<Vec<_>>(), vec![
+            vec![0, 9, 8],
+            vec![0, 9, 9],
+            vec![1, 0, 0],
+            vec![1, 0, 1],
+        ]);
+        let pi = PasswordIterator::new(12345678, 12345679, 8);
+        assert_eq!(pi.collect::<Vec<_>>(), vec![
+            vec![1, 2, 3, 4, 5, 6, 7, 8],
+            vec![1, 2, 3, 4, 5, 6, 7, 9],
+        ]);
+    }
+
+    #[test]
+    fn test_monotonic_password_iterator() {
+        let pi = "0097-0123".parse::<MonotonicPasswordIterator>().unwrap();
+        assert_eq!(pi.collect::<Vec<_>>(), vec![
+            vec![0, 0, 9, 9],
+            vec![0, 1, 1, 1],
+            vec![0, 1, 1, 2],
+            vec![0, 1, 1, 3],
+            vec![0, 1, 1, 4],
+            vec![0, 1, 1, 5],
+            vec![0, 1, 1, 6],
+            vec![0, 1, 1, 7],
+            vec![0, 1, 1, 8],
+            vec![0, 1, 1, 9],
+            vec![0, 1, 2, 2],
+            vec![0, 1, 2, 3],
+        ]);
+        assert_eq!("999-999".parse::<MonotonicPasswordIterator>().unwrap().count(), 1);
+        assert_eq!("990-998".parse::<MonotonicPasswordIterator>().unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_monotonic_password_iterator_matches_filter() {
+        for range in ["152085-670283", "0-9999", "111111-111111", "123456-123457"].iter() {
+            let filtered: Vec<Vec<u8>> = range.parse::<PasswordIterator>().unwrap()
+                .filter(|x| never_decreasing(x))
+                .collect();
+            let generated: Vec<Vec<u8>> = range.parse::<MonotonicPasswordIterator>().unwrap().collect();
+            assert_eq!(generated, filtered, "range {}", range);
+        }
+    }
+
+    #[test]
+    fn test_count_matching_monotonic_matches_filter() {
+        let matcher = AndMatcher(&[
+            &FunctionMatcher(never_decreasing),
+            &FunctionMatcher(has_double),
+        ]);
+        for range in ["152085-670283", "0-9999", "100-200"].iter() {
+            assert_eq!(count_matching_monotonic(range, &matcher).unwrap(),
+                       count_matching(range, &matcher).unwrap(),
+                       "range {}", range);
+        }
+    }
+
+    #[test]
+    fn test_count_matching_any_matcher() {
+        // 100, 110 ..= 119, 122, 133, ..., 199, 200 - including decreasing values like 110
+        let matcher = FunctionMatcher(has_double);
+        assert_eq!(count_matching("100-200", &matcher).unwrap(), 20);
+    }
+
+    #[test]
+    fn test_count_matching_part1_examples() {
+        let matcher = AndMatcher(&[
+            &FunctionMatcher(never_decreasing),
+            &FunctionMatcher(has_double),
+        ]);
+        assert_eq!(count_matching("111111-111111", &matcher).unwrap(), 1);
+        assert_eq!(count_matching("223450-223450", &matcher).unwrap(), 0);
+        assert_eq!(count_matching("123789-123789", &matcher).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_count_matching_part2_examples() {
+        let matcher = AndMatcher(&[
+            &FunctionMatcher(never_decreasing),
+            &FunctionMatcher(has_isolated_double),
+        ]);
+        assert_eq!(count_matching("112233-112233", &matcher).unwrap(), 1);
+        assert_eq!(count_matching("123444-123444", &matcher).unwrap(), 0);
+        assert_eq!(count_matching("111122-111122", &matcher).unwrap(), 1);
+    }
+
     #[test]
     fn test_part1() {
-        assert_eq!(part1(), 1764);
+        assert_eq!(part1().unwrap(), 1764);
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(), 1196);
+        assert_eq!(part2().unwrap(), 1196);
     }
 }
\ No newline at end of file
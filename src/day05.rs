@@ -1,30 +1,56 @@
+use crate::error::{AocError, Result};
 use crate::intcode;
 
-pub fn part1() -> intcode::Word {
-    let mut emulator = intcode::Emulator::from_data_file("day05_input.txt");
-    emulator.write(1);
+/// Run the diagnostic program with system ID `input`, returning every output
+pub fn run_with_input(filename: &str, input: intcode::Word) -> Result<Vec<intcode::Word>> {
+    let mut emulator = intcode::Emulator::from_data_file(filename)?;
+    emulator.write(input);
     emulator.run();
-    *emulator.read_all().last().unwrap()
+    Ok(emulator.read_all())
 }
 
-pub fn part2() -> intcode::Word {
-    let mut emulator = intcode::Emulator::from_data_file("day05_input.txt");
-    emulator.write(5);
-    emulator.run();
-    *emulator.read_all().last().unwrap()
+/// Get the diagnostic code, i.e. the last output, for system ID `input`
+fn diagnostic_code(filename: &str, input: intcode::Word) -> Result<intcode::Word> {
+    run_with_input(filename, input)?.last().cloned().ok_or_else(|| AocError::Intcode("no output".to_string()))
+}
+
+pub fn solve_part1(filename: &str) -> Result<intcode::Word> {
+    diagnostic_code(filename, 1)
+}
+
+pub fn part1() -> Result<intcode::Word> {
+    solve_part1("day05_input.txt")
+}
+
+pub fn solve_part2(filename: &str) -> Result<intcode::Word> {
+    diagnostic_code(filename, 5)
+}
+
+pub fn part2() -> Result<intcode::Word> {
+    solve_part2("day05_input.txt")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_run_with_input_diagnostics() {
+        let output = run_with_input("day05_input.txt", 1).unwrap();
+        let (code, tests) = output.split_last().unwrap();
+        assert!(!tests.is_empty());
+        assert!(tests.iter().all(|&x| x == 0));
+        assert_eq!(*code, 15508323);
+        assert_eq!(run_with_input("day05_input.txt", 5).unwrap(), vec![9006327]);
+    }
+
     #[test]
     fn test_part1() {
-        assert_eq!(part1(), 15508323);
+        assert_eq!(part1().unwrap(), 15508323);
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(), 9006327);
+        assert_eq!(part2().unwrap(), 9006327);
     }
 }
@@ -1,6 +1,7 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::str::FromStr;
 
+use crate::error::{AocError, Result};
 use crate::util;
 
 #[derive(Debug)]
@@ -10,27 +11,35 @@ struct Orbit {
 }
 
 impl FromStr for Orbit {
-    type Err = ();
+    type Err = AocError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let index = s.find(")").unwrap();
+        let index = s.find(")").ok_or_else(|| AocError::Parse(format!("invalid orbit: {}", s)))?;
+        let (parent, body) = (&s[0 .. index], &s[index+1 ..]);
+        if parent.is_empty() || body.is_empty() {
+            return Err(AocError::Parse(format!("invalid orbit: {}", s)));
+        }
         Ok(Orbit {
-            parent: s[0 .. index].to_string(),
-            body: s[index+1 ..].to_string(),
+            parent: parent.to_string(),
+            body: body.to_string(),
         })
     }
 }
 
 #[derive(Debug)]
 struct OrbitMap {
+    /// Bodies connected by an orbit, in either direction
     adjacent: HashMap<String, HashSet<String>>,
+    /// Bodies directly orbiting each body
+    orbiters: HashMap<String, HashSet<String>>,
 }
 
 impl OrbitMap {
     fn new(orbits: &Vec<Orbit>) -> OrbitMap {
-        let mut map = OrbitMap { adjacent: HashMap::new() };
+        let mut map = OrbitMap { adjacent: HashMap::new(), orbiters: HashMap::new() };
         for orbit in orbits {
             map.add_adjacent(orbit.parent.as_str(), orbit.body.as_str());
+            map.orbiters.entry(orbit.parent.clone()).or_default().insert(orbit.body.clone());
         }
         map
     }
@@ -51,78 +60,192 @@ impl OrbitMap {
         self.adjacent.get(k)
     }
 
-    fn get_distances_from(&self, k: &str) -> HashMap<String, usize> {
+    /// Get the distance from `k` to itself and every body directly or indirectly orbiting it, or
+    /// `None` if `k` isn't in the map
+    fn get_distances_from(&self, k: &str) -> Option<HashMap<String, usize>> {
+        if !self.adjacent.contains_key(k) {
+            return None;
+        }
         let mut distances: HashMap<String, usize> = HashMap::new();
         let mut queue: VecDeque<(&HashSet<String>, usize)> = VecDeque::new();
         let start: HashSet<String> = vec![k.to_string()].into_iter().collect();
         queue.push_back((&start, 0));
-        loop {
-            match queue.pop_front() {
-                Some((bodies, depth)) => {
-                    for body in bodies {
-                        if !distances.contains_key(body.as_str()) {
-                            distances.insert(body.clone(), depth);
-                            match self.get_adjacent(body.as_str()) {
-                                Some(next) => {
-                                    queue.push_back((next, depth + 1));
-                                },
-                                None => (),
-                            }
-                        }
+        while let Some((bodies, depth)) = queue.pop_front() {
+            for body in bodies {
+                if !distances.contains_key(body.as_str()) {
+                    distances.insert(body.clone(), depth);
+                    if let Some(next) = self.orbiters.get(body.as_str()) {
+                        queue.push_back((next, depth + 1));
                     }
-                },
-                None => break,
+                }
+            }
+        }
+        Some(distances)
+    }
+
+    /// Count the bodies at each depth below `root`, including `root` itself at depth 0
+    ///
+    /// Empty if `root` isn't in the map.
+    fn depth_histogram(&self, root: &str) -> BTreeMap<usize, usize> {
+        let mut histogram: BTreeMap<usize, usize> = BTreeMap::new();
+        for depth in self.get_distances_from(root).unwrap_or_default().values() {
+            *histogram.entry(*depth).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Find the sequence of bodies from `a` to `b` inclusive, or `None` if they're not connected
+    fn path_between(&self, a: &str, b: &str) -> Option<Vec<String>> {
+        // Breadth-first search, recording the body each body was first reached from
+        let mut previous: HashMap<&str, Option<&str>> = HashMap::new();
+        let mut queue: VecDeque<&str> = VecDeque::new();
+        previous.insert(a, None);
+        queue.push_back(a);
+        while let Some(body) = queue.pop_front() {
+            if body == b {
+                break;
+            }
+            for next in self.get_adjacent(body).into_iter().flatten() {
+                if !previous.contains_key(next.as_str()) {
+                    previous.insert(next.as_str(), Some(body));
+                    queue.push_back(next.as_str());
+                }
             }
         }
-        distances
+        // Walk back from the destination to reconstruct the path
+        let mut path: Vec<String> = Vec::new();
+        let mut current = if previous.contains_key(b) { Some(b) } else { None };
+        while let Some(body) = current {
+            path.push(body.to_string());
+            current = previous[body];
+        }
+        if path.is_empty() {
+            None
+        } else {
+            path.reverse();
+            Some(path)
+        }
     }
 }
 
-fn count_orbits(filename: &str) -> usize {
-    let orbits: Vec<Orbit> = util::read_data(filename);
+/// Count direct and indirect orbits of `root` by every body in its subtree
+fn count_orbits_from(filename: &str, root: &str) -> Result<usize> {
+    let orbits: Vec<Orbit> = util::read_data(filename)?;
     let map =  OrbitMap::new(&orbits);
-    let distances = map.get_distances_from("COM");
-    distances.values().sum()
+    let histogram = map.depth_histogram(root);
+    if histogram.is_empty() {
+        return Err(AocError::NoSolution(format!("{} not found", root)));
+    }
+    Ok(histogram.iter().map(|(depth, n)| depth * n).sum())
 }
 
-fn get_orbital_transfers(filename: &str, a: &str, b: &str) -> usize {
-    let orbits: Vec<Orbit> = util::read_data(filename);
+fn get_orbital_transfers(filename: &str, a: &str, b: &str) -> Result<usize> {
+    let orbits: Vec<Orbit> = util::read_data(filename)?;
     let map =  OrbitMap::new(&orbits);
-    let distances = map.get_distances_from(a);
-    *distances.get(b).unwrap() - 2
+    let path = map.path_between(a, b)
+        .ok_or_else(|| AocError::NoSolution(format!("no path from {} to {}", a, b)))?;
+    // Transfers are between the bodies `a` and `b` orbit, so exclude the edges to `a` and `b`
+    path.len().checked_sub(3)
+        .ok_or_else(|| AocError::NoSolution(format!("{} and {} don't orbit separate bodies", a, b)))
+}
+
+pub fn solve_part1(filename: &str) -> Result<usize> {
+    count_orbits_from(filename, "COM")
+}
+
+pub fn part1() -> Result<usize> {
+    solve_part1("day06_input.txt")
 }
 
-pub fn part1() -> usize {
-    count_orbits("day06_input.txt")
+pub fn solve_part2(filename: &str) -> Result<usize> {
+    get_orbital_transfers(filename, "YOU", "SAN")
 }
 
-pub fn part2() -> usize {
-    get_orbital_transfers("day06_input.txt", "YOU", "SAN")
+pub fn part2() -> Result<usize> {
+    solve_part2("day06_input.txt")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_orbit() {
+        let orbit: Orbit = "COM)B".parse().unwrap();
+        assert_eq!((orbit.parent.as_str(), orbit.body.as_str()), ("COM", "B"));
+        assert!(matches!("ABCD".parse::<Orbit>(), Err(AocError::Parse(_))));
+        assert!(matches!(")B".parse::<Orbit>(), Err(AocError::Parse(_))));
+        assert!(matches!("COM)".parse::<Orbit>(), Err(AocError::Parse(_))));
+        assert!(matches!("".parse::<Orbit>(), Err(AocError::Parse(_))));
+    }
+
     #[test]
     fn test_count_orbits() {
-        assert_eq!(count_orbits("day06_example1.txt"), 42);
+        assert_eq!(count_orbits_from("day06_example1.txt", "COM").unwrap(), 42);
+    }
+
+    #[test]
+    fn test_count_orbits_subtree() {
+        // D is orbited by E and I, E by F and J, J by K, and K by L
+        assert_eq!(count_orbits_from("day06_example1.txt", "D").unwrap(), 1 + 1 + 2 + 2 + 3 + 4);
+        assert_eq!(count_orbits_from("day06_example1.txt", "L").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_get_distances_from_missing() {
+        let orbits: Vec<Orbit> = util::read_data("day06_example1.txt").unwrap();
+        let map = OrbitMap::new(&orbits);
+        assert_eq!(map.get_distances_from("NOWHERE"), None);
+    }
+
+    #[test]
+    fn test_depth_histogram() {
+        let orbits: Vec<Orbit> = util::read_data("day06_example1.txt").unwrap();
+        let map = OrbitMap::new(&orbits);
+        let histogram = map.depth_histogram("COM");
+        let expected: BTreeMap<usize, usize> =
+            vec![(0, 1), (1, 1), (2, 2), (3, 2), (4, 2), (5, 2), (6, 1), (7, 1)].into_iter().collect();
+        assert_eq!(histogram, expected);
+        assert_eq!(histogram.values().sum::<usize>(), orbits.len() + 1);
+        assert_eq!(histogram.iter().map(|(depth, n)| depth * n).sum::<usize>(), 42);
+        assert!(map.depth_histogram("NOWHERE").is_empty());
     }
 
     #[test]
     fn test_get_orbital_transfers() {
-        assert_eq!(get_orbital_transfers("day06_example2.txt", "YOU", "SAN"), 4);
-        assert_eq!(get_orbital_transfers("day06_input.txt", "YOU", "SAN"),
-                   get_orbital_transfers("day06_input.txt", "SAN", "YOU"));
+        assert_eq!(get_orbital_transfers("day06_example2.txt", "YOU", "SAN").unwrap(), 4);
+        assert_eq!(get_orbital_transfers("day06_input.txt", "YOU", "SAN").unwrap(),
+                   get_orbital_transfers("day06_input.txt", "SAN", "YOU").unwrap());
+        // Both orbit K, so no transfers needed
+        assert_eq!(get_orbital_transfers("day06_example2.txt", "YOU", "L").unwrap(), 0);
+        // YOU orbits K directly
+        assert!(matches!(get_orbital_transfers("day06_example2.txt", "YOU", "K"), Err(AocError::NoSolution(_))));
+        assert!(matches!(get_orbital_transfers("day06_example2.txt", "YOU", "YOU"), Err(AocError::NoSolution(_))));
+    }
+
+    #[test]
+    fn test_path_between() {
+        let orbits: Vec<Orbit> = util::read_data("day06_example2.txt").unwrap();
+        let map = OrbitMap::new(&orbits);
+        let path = map.path_between("YOU", "SAN").unwrap();
+        assert_eq!(path, vec!["YOU", "K", "J", "E", "D", "I", "SAN"]);
+        assert_eq!(map.path_between("COM", "COM"), Some(vec!["COM".to_string()]));
+        assert_eq!(map.path_between("COM", "NOWHERE"), None);
+    }
+
+    #[test]
+    fn test_solve_examples() {
+        assert_eq!(solve_part1("day06_example1.txt").unwrap(), 42);
+        assert_eq!(solve_part2("day06_example2.txt").unwrap(), 4);
     }
 
     #[test]
     fn test_part1() {
-        assert_eq!(part1(), 119831);
+        assert_eq!(part1().unwrap(), 119831);
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(), 322);
+        assert_eq!(part2().unwrap(), 322);
     }
 }
@@ -1,5 +1,5 @@
 use permutohedron::Heap;
-use crate::intcode::{Word, Emulator};
+use crate::intcode::{Word, Emulator, State};
 
 fn amp_chain(base: &Emulator, phases: &[Word]) -> Word {
     let mut signal: Word = 0;
@@ -30,21 +30,23 @@ fn amp_feedback_loop(base: &Emulator, phases: &[Word]) -> Word {
         })
         .collect();
     let mut thruster_signal: Word = 0;
-    let mut signal: Word = 0;
-    'outer: loop {
+    let mut signal: Option<Word> = Some(0);
+    loop {
+        // Each amp produces one signal per turn, or nothing once it has halted
+        let mut state = State::Continue;
         for amp in amps.iter_mut() {
-            amp.write(signal);
-            amp.run();      // Until halts or waits on new input
-            if let Some(v) = amp.read() {
-                signal = v;
-            } else {
-                // No output means it's halted and we read its last output already
-                break 'outer;
+            if let Some(v) = signal {
+                amp.write(v);
             }
+            state = amp.run_until_output(1);
+            signal = amp.read();
+        }
+        // Finished when the last amp halts, and its last output was the thruster signal
+        if state == State::Halt {
+            return thruster_signal;
         }
-        thruster_signal = signal;
+        thruster_signal = signal.expect("amp needs input but produced no output");
     }
-    thruster_signal
 }
 
 fn run_amp_feedback_loops(base: &Emulator) -> Word {
@@ -53,12 +55,20 @@ fn run_amp_feedback_loops(base: &Emulator) -> Word {
     heap.map(|phases| amp_feedback_loop(base, phases.as_slice())).max().unwrap()
 }
 
+pub fn solve_part1(filename: &str) -> Word {
+    run_amp_chains(&Emulator::from_data_file(filename).unwrap())
+}
+
 pub fn part1() -> Word {
-    run_amp_chains(&Emulator::from_data_file("day07_input.txt"))
+    solve_part1("day07_input.txt")
+}
+
+pub fn solve_part2(filename: &str) -> Word {
+    run_amp_feedback_loops(&Emulator::from_data_file(filename).unwrap())
 }
 
 pub fn part2() -> Word {
-    run_amp_feedback_loops(&Emulator::from_data_file("day07_input.txt"))
+    solve_part2("day07_input.txt")
 }
 
 #[cfg(test)]
@@ -67,36 +77,78 @@ mod tests {
 
     #[test]
     fn test_run_amp_chains_1() {
-        let base = Emulator::from_data_file("day07_example1.txt");
+        let base = Emulator::from_data_file("day07_example1.txt").unwrap();
         assert_eq!(run_amp_chains(&base), 43210);
     }
 
     #[test]
     fn test_run_amp_chains_2() {
-        let base = Emulator::from_data_file("day07_example2.txt");
+        let base = Emulator::from_data_file("day07_example2.txt").unwrap();
         assert_eq!(run_amp_chains(&base), 54321);
     }
 
     #[test]
     fn test_run_amp_chains_3() {
-        let base = Emulator::from_data_file("day07_example3.txt");
+        let base = Emulator::from_data_file("day07_example3.txt").unwrap();
         assert_eq!(run_amp_chains(&base), 65210);
     }
 
     #[test]
     fn test_run_amp_feedback_loops_1() {
-        let base = Emulator::from_data_file("day07_example4.txt");
+        let base = Emulator::from_data_file("day07_example4.txt").unwrap();
         assert_eq!(amp_feedback_loop(&base, &[9, 8, 7, 6, 5]), 139629729);
         assert_eq!(run_amp_feedback_loops(&base), 139629729);
     }
 
     #[test]
     fn test_run_amp_feedback_loops_2() {
-        let base = Emulator::from_data_file("day07_example5.txt");
+        let base = Emulator::from_data_file("day07_example5.txt").unwrap();
         assert_eq!(amp_feedback_loop(&base, &[9, 7, 8, 5, 6]), 18216);
         assert_eq!(run_amp_feedback_loops(&base), 18216);
     }
 
+    /// The original feedback loop, running each amp until it needs input
+    fn amp_feedback_loop_run(base: &Emulator, phases: &[Word]) -> Word {
+        let mut amps: Vec<Emulator> = phases.iter().map(|phase| {
+            let mut amp = base.clone();
+            amp.write(*phase);
+            amp
+        }).collect();
+        let mut thruster_signal: Word = 0;
+        let mut signal: Word = 0;
+        'outer: loop {
+            for amp in amps.iter_mut() {
+                amp.write(signal);
+                amp.run();
+                if let Some(v) = amp.read() {
+                    signal = v;
+                } else {
+                    break 'outer;
+                }
+            }
+            thruster_signal = signal;
+        }
+        thruster_signal
+    }
+
+    #[test]
+    fn test_amp_feedback_loop_matches_original() {
+        for filename in &["day07_example4.txt", "day07_example5.txt", "day07_input.txt"] {
+            let base = Emulator::from_data_file(filename).unwrap();
+            let mut phases: Vec<Word> = (5 .. 10).collect();
+            let heap = Heap::new(&mut phases);
+            for phases in heap {
+                assert_eq!(amp_feedback_loop(&base, &phases), amp_feedback_loop_run(&base, &phases));
+            }
+        }
+    }
+
+    #[test]
+    fn test_solve_examples() {
+        assert_eq!(solve_part1("day07_example1.txt"), 43210);
+        assert_eq!(solve_part2("day07_example4.txt"), 139629729);
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(), 46248);
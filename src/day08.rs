@@ -1,9 +1,82 @@
+use std::convert::TryFrom;
+use std::fmt::{self, Display, Formatter};
+use crate::error::{AocError, Result};
 use crate::util;
 
 const WIDTH: usize = 25;
 const HEIGHT: usize = 6;
-const SIZE: usize = WIDTH * HEIGHT;
 
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+enum Pixel {
+    Black,
+    White,
+    Transparent,
+}
+
+impl TryFrom<u8> for Pixel {
+    type Error = AocError;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            b'0' => Ok(Pixel::Black),
+            b'1' => Ok(Pixel::White),
+            b'2' => Ok(Pixel::Transparent),
+            _ => Err(AocError::Parse(format!("invalid pixel {:?}", value as char))),
+        }
+    }
+}
+
+/// A `width` x `height` image made of one or more layers, the first layer on top
+#[derive(Clone,Debug,Eq,PartialEq)]
+struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Pixel>,
+}
+
+impl Image {
+    fn from_layers(data: &[u8], width: usize, height: usize) -> Result<Image> {
+        if data.len() % (width * height) != 0 {
+            return Err(AocError::Parse(format!("{} pixels isn't a whole number of {}x{} layers", data.len(), width, height)));
+        }
+        let pixels = data.iter().map(|&b| Pixel::try_from(b)).collect::<Result<_>>()?;
+        Ok(Image { width, height, pixels })
+    }
+
+    fn layers(&self) -> impl Iterator<Item=&[Pixel]> {
+        self.pixels.chunks(self.width * self.height)
+    }
+
+    /// Stack all the layers into a single layer, where each pixel is the first non-transparent
+    /// pixel in that position
+    fn flatten(&self) -> Image {
+        let mut current = vec![Pixel::Transparent; self.width * self.height];
+        for layer in self.layers() {
+            for (a, b) in current.iter_mut().zip(layer.iter()) {
+                if *a == Pixel::Transparent {
+                    *a = *b;
+                }
+            }
+        }
+        Image { width: self.width, height: self.height, pixels: current }
+    }
+
+    /// Render the top layer as rows of `X` (white) and space (black or transparent)
+    fn render(&self) -> Vec<String> {
+        self.pixels[.. self.width * self.height]
+            .chunks(self.width)
+            .map(|x| x.iter().map(|p| if *p == Pixel::White { 'X' } else { ' ' }).collect())
+            .collect()
+    }
+}
+
+impl Display for Image {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render().join("\n"))
+    }
+}
+
+// Digits counted for the checksum, not necessarily valid pixels
 const BLACK: u8 = b'0';
 const WHITE: u8 = b'1';
 const TRANSPARENT: u8 = b'2';
@@ -12,54 +85,66 @@ fn count_byte(layer: &[u8], byte: u8) -> usize {
     layer.iter().filter(|&x| *x == byte).count()
 }
 
-fn merge_layers(current: &mut [u8], next: &[u8]) {
-    for (a, b) in current.iter_mut().zip(next.iter()) {
-        if *a == TRANSPARENT {
-            *a = *b;
-        }
-    }
-}
-
-fn get_checksum(data: &[u8]) -> usize {
+fn get_checksum(data: &[u8], width: usize, height: usize) -> usize {
     data
-        .chunks(SIZE)
+        .chunks(width * height)
         .min_by_key(|&chunk| count_byte(chunk, BLACK))
         .map(|chunk| count_byte(chunk, WHITE) * count_byte(chunk, TRANSPARENT))
         .unwrap()
 }
 
-pub fn part1() -> usize {
-    let data = util::read_lines("day08_input.txt").into_iter().nth(0).unwrap().into_bytes();
-    get_checksum(data.as_slice())
+pub fn solve_part1(filename: &str) -> Result<usize> {
+    let data = util::read_one::<String>(filename)?.into_bytes();
+    Ok(get_checksum(data.as_slice(), WIDTH, HEIGHT))
 }
 
-pub fn part2() -> String {
-    let data = util::read_lines("day08_input.txt").into_iter().nth(0).unwrap().into_bytes();
-    let layers: Vec<&[u8]> = data.chunks(SIZE).collect();
-    let mut current: [u8; SIZE] = [TRANSPARENT; SIZE];
-    for layer in layers {
-        merge_layers(&mut current, layer);
-    }
-    let strings: Vec<String> =
-        current
-        .chunks(WIDTH)
-        .map(|x| x.iter().map(|c| if *c == WHITE { 'X' } else { ' ' }).collect())
-        .collect();
-    format!("\n{}\n", strings.join("\n"))
+pub fn part1() -> Result<usize> {
+    solve_part1("day08_input.txt")
+}
+
+pub fn solve_part2(filename: &str) -> Result<String> {
+    let data = util::read_one::<String>(filename)?.into_bytes();
+    let image = Image::from_layers(data.as_slice(), WIDTH, HEIGHT)?.flatten();
+    util::dump_ppm("day08", &image.render())?;
+    Ok(format!("\n{}\n", image))
+}
+
+pub fn part2() -> Result<String> {
+    solve_part2("day08_input.txt")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_get_checksum_example() {
+        assert_eq!(get_checksum(b"123456789012", 3, 2), 1);
+    }
+
+    #[test]
+    fn test_from_layers_invalid() {
+        assert!(matches!(Image::from_layers(b"123456789012", 3, 2), Err(AocError::Parse(_))));
+        assert!(matches!(Image::from_layers(b"01201", 3, 2), Err(AocError::Parse(_))));
+    }
+
+    #[test]
+    fn test_flatten_example() {
+        let image = Image::from_layers(b"0222112222120000", 2, 2).unwrap().flatten();
+        assert_eq!(image.pixels, vec![Pixel::Black, Pixel::White, Pixel::White, Pixel::Black]);
+        assert_eq!(image.layers().count(), 1);
+        assert_eq!(image.render(), vec![" X", "X "]);
+        assert_eq!(image.to_string(), " X\nX ");
+    }
+
     #[test]
     fn test_part1() {
-        assert_eq!(part1(), 2250);
+        assert_eq!(part1().unwrap(), 2250);
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(), format!("\n{}\n", vec![
+        assert_eq!(part2().unwrap(), format!("\n{}\n", vec![
             "XXXX X  X   XX X  X X    ",
             "X    X  X    X X  X X    ",
             "XXX  XXXX    X X  X X    ",
@@ -68,4 +153,10 @@ mod tests {
             "X    X  X  XX   XX  XXXX ",
         ].join("\n")));
     }
+
+    #[test]
+    fn test_part2_ocr() {
+        let image: Vec<String> = part2().unwrap().lines().filter(|x| !x.is_empty()).map(String::from).collect();
+        assert_eq!(util::ocr_letters(&image), "FHJUL");
+    }
 }
@@ -1,23 +1,33 @@
 use crate::intcode::*;
 
-pub fn part1() -> Word {
-    let mut emulator = Emulator::from_data_file("day09_input.txt");
-    emulator.write(1);
+/// Run the BOOST program with `input`, returning every output
+pub fn run_with_input(filename: &str, input: Word) -> Vec<Word> {
+    let mut emulator = Emulator::from_data_file(filename).unwrap();
+    emulator.write(input);
     emulator.run();
-    let output = emulator.read_all().to_vec();
+    emulator.read_all()
+}
+
+pub fn solve_part1(filename: &str) -> Word {
+    let output = run_with_input(filename, 1);
     assert_eq!(output.len(), 1);
     output[0]
 }
 
-pub fn part2() -> Word {
-    let mut emulator = Emulator::from_data_file("day09_input.txt");
-    emulator.write(2);
-    emulator.run();
-    let output = emulator.read_all().to_vec();
+pub fn part1() -> Word {
+    solve_part1("day09_input.txt")
+}
+
+pub fn solve_part2(filename: &str) -> Word {
+    let output = run_with_input(filename, 2);
     assert_eq!(output.len(), 1);
     output[0]
 }
 
+pub fn part2() -> Word {
+    solve_part2("day09_input.txt")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -4,7 +4,7 @@ use crate::util;
 use crate::util::{Point2D, Vector2D};
 
 fn read_asteroids(filename: &str) -> Vec<Point2D> {
-    let lines = util::read_lines(filename);
+    let lines = util::read_lines(filename).unwrap();
     lines
         .iter()
         .enumerate()
@@ -45,6 +45,17 @@ fn max_visible(asteroids: &[Point2D]) -> (usize, usize) {
         .unwrap()
 }
 
+/// Get every asteroid with the number of asteroids visible from it, most visible first
+///
+/// Ties are ordered by position, top-to-bottom then left-to-right.
+pub fn visibility_ranking(asteroids: &[Point2D]) -> Vec<(Point2D, usize)> {
+    let mut ranking: Vec<(Point2D, usize)> = (0..asteroids.len())
+        .map(|i| (asteroids[i], count_visible_from(i, asteroids)))
+        .collect();
+    ranking.sort_by_key(|(p, n)| (Reverse(*n), p.y, p.x));
+    ranking
+}
+
 type InventoryItem = (Vector2D, Vec<Vector2D>);
 type Inventory = Vec<InventoryItem>;
 
@@ -67,8 +78,11 @@ fn vector_angle(v: &Vector2D) -> f64 {
     -((v.x as f64).atan2(v.y as f64))
 }
 
+/// Sort directions clockwise from up, breaking (unexpected) ties by distance so the order is total
 fn sort_inventory(data: &mut Inventory) {
-    data.sort_by(|(a, _), (b, _)| vector_angle(a).partial_cmp(&vector_angle(b)).unwrap());
+    data.sort_by(|(a, _), (b, _)| {
+        vector_angle(a).total_cmp(&vector_angle(b)).then_with(|| a.manhattan_length().cmp(&b.manhattan_length()))
+    });
 }
 
 struct ShootingIterator<'a> {
@@ -102,26 +116,77 @@ impl<'a> Iterator for ShootingIterator<'a> {
     }
 }
 
+/// Get every asteroid in the order it's vaporized by a laser at the asteroid at index `i`
+pub fn vaporization_order(i: usize, asteroids: &[Point2D]) -> Vec<Point2D> {
+    let station = asteroids[i];
+    let mut inventory = inventory(i, asteroids);
+    sort_inventory(&mut inventory);
+    ShootingIterator::new(&mut inventory).map(|v| station + v).collect()
+}
+
+/// The asteroids in a map, with queries about what's visible from them
+pub struct AsteroidField {
+    asteroids: Vec<Point2D>,
+}
+
+impl AsteroidField {
+    pub fn from_file(filename: &str) -> AsteroidField {
+        AsteroidField { asteroids: read_asteroids(filename) }
+    }
+
+    fn index_of(&self, station: &Point2D) -> usize {
+        self.asteroids.iter().position(|a| a == station).expect("station must be an asteroid")
+    }
+
+    /// Get the asteroid that can see the most other asteroids, and how many it can see
+    pub fn best_station(&self) -> (Point2D, usize) {
+        let (i, n) = max_visible(self.asteroids.as_slice());
+        (self.asteroids[i], n)
+    }
+
+    /// Count the asteroids visible from the asteroid at `station`
+    pub fn visible_from(&self, station: &Point2D) -> usize {
+        count_visible_from(self.index_of(station), self.asteroids.as_slice())
+    }
+
+    /// Get every other asteroid in the order it's vaporized by a laser at `station`
+    pub fn targets_from(&self, station: &Point2D) -> Vec<Point2D> {
+        vaporization_order(self.index_of(station), self.asteroids.as_slice())
+    }
+}
+
+pub fn solve_part1(filename: &str) -> usize {
+    AsteroidField::from_file(filename).best_station().1
+}
+
 pub fn part1() -> usize {
-    let asteroids = read_asteroids("day10_input.txt");
-    max_visible(asteroids.as_slice()).1
+    solve_part1("day10_input.txt")
 }
 
-pub fn part2() -> i32 {
-    let asteroids = read_asteroids("day10_input.txt");
-    let (i, _) = max_visible(asteroids.as_slice());
-    let mut inventory = inventory(i, asteroids.as_slice());
-    sort_inventory(&mut inventory);
-    let mut it = ShootingIterator::new(&mut inventory);
-    let last_relative = it.nth(199).unwrap();
-    let last = asteroids[i] + last_relative;
+pub fn solve_part2(filename: &str) -> i32 {
+    let field = AsteroidField::from_file(filename);
+    let (station, _) = field.best_station();
+    let last = field.targets_from(&station)[199];
     last.x * 100 + last.y
 }
 
+pub fn part2() -> i32 {
+    solve_part2("day10_input.txt")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_count_visible_from_duplicate_station() {
+        // A duplicate of the station is in no particular direction, so counts as one more asteroid
+        let mut asteroids = read_asteroids("day10_example1.txt");
+        let (i, n) = max_visible(asteroids.as_slice());
+        asteroids.push(asteroids[i]);
+        assert_eq!(count_visible_from(i, asteroids.as_slice()), n + 1);
+    }
+
     #[test]
     fn test_max_visible_example1() {
         let asteroids = read_asteroids("day10_example1.txt");
@@ -146,6 +211,16 @@ mod tests {
         assert_eq!(max_visible(asteroids.as_slice()).1, 210);
     }
 
+    #[test]
+    fn test_visibility_ranking_example1() {
+        let asteroids = read_asteroids("day10_example1.txt");
+        let ranking = visibility_ranking(asteroids.as_slice());
+        let (i, n) = max_visible(asteroids.as_slice());
+        assert_eq!(ranking.len(), asteroids.len());
+        assert_eq!(ranking[0], (asteroids[i], n));
+        assert!(ranking.windows(2).all(|w| w[0].1 >= w[1].1));
+    }
+
     #[test]
     fn test_vector_angle() {
         let a0 = vector_angle(&vector!(0, -1));
@@ -159,6 +234,29 @@ mod tests {
         assert!(a3 < a4);
     }
 
+    #[test]
+    fn test_sort_inventory_ties() {
+        // Equal angles shouldn't happen for distinct unit vectors, but must still sort consistently
+        let mut inventory: Inventory = vec![
+            (vector!(0, 2), vec![]),
+            (vector!(-1, 0), vec![]),
+            (vector!(0, 1), vec![]),
+            (vector!(0, -1), vec![]),
+        ];
+        sort_inventory(&mut inventory);
+        let order: Vec<Vector2D> = inventory.into_iter().map(|(v, _)| v).collect();
+        assert_eq!(order, vec![vector!(0, -1), vector!(0, 1), vector!(0, 2), vector!(-1, 0)]);
+    }
+
+    #[test]
+    fn test_vaporization_order_same_angle() {
+        // Nearer asteroid in each direction fires first, then the laser goes round again
+        let asteroids = vec![point!(2, 4), point!(2, 0), point!(2, 2), point!(4, 4), point!(3, 4)];
+        assert_eq!(vaporization_order(0, asteroids.as_slice()), vec![
+            point!(2, 2), point!(3, 4), point!(2, 0), point!(4, 4),
+        ]);
+    }
+
     #[test]
     fn test_shooting_iterator_example4() {
         let asteroids = read_asteroids("day10_example4.txt");
@@ -176,6 +274,41 @@ mod tests {
         assert_eq!(results[99], point!(10, 16) - station);
     }
 
+    #[test]
+    fn test_vaporization_order_example4() {
+        let asteroids = read_asteroids("day10_example4.txt");
+        let (station_index, _) = max_visible(asteroids.as_slice());
+        let order = vaporization_order(station_index, asteroids.as_slice());
+        assert_eq!(order.len(), asteroids.len() - 1);
+        assert_eq!(order[0], point!(11, 12));
+        assert_eq!(order[1], point!(12, 1));
+        assert_eq!(order[2], point!(12, 2));
+        assert_eq!(order[9], point!(12, 8));
+        assert_eq!(order[19], point!(16, 0));
+        assert_eq!(order[49], point!(16, 9));
+        assert_eq!(order[99], point!(10, 16));
+        assert_eq!(order[198], point!(9, 6));
+        assert_eq!(order[199], point!(8, 2));
+        assert_eq!(order[200], point!(10, 9));
+        assert_eq!(order[298], point!(11, 1));
+    }
+
+    #[test]
+    fn test_solve_example4() {
+        assert_eq!(solve_part1("day10_example4.txt"), 210);
+        assert_eq!(solve_part2("day10_example4.txt"), 802);
+    }
+
+    #[test]
+    fn test_asteroid_field() {
+        let field = AsteroidField::from_file("day10_example1.txt");
+        assert_eq!(field.best_station(), (point!(5, 8), 33));
+        assert_eq!(field.visible_from(&point!(5, 8)), 33);
+        let field = AsteroidField::from_file("day10_example4.txt");
+        assert_eq!(field.best_station(), (point!(11, 13), 210));
+        assert_eq!(field.visible_from(&point!(11, 13)), 210);
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(), 326);
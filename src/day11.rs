@@ -1,127 +1,233 @@
-use std::cmp::{min, max};
-use std::collections::HashMap;
+use crate::error::Result;
 use crate::intcode::{Emulator, Word, State};
-use crate::util::{Point2D, Vector2D};
+use crate::util::{self, Canvas, Point2D, Vector2D};
 
-#[allow(dead_code)]
-const BLACK: Word = 0;
-const WHITE: Word = 1;
+pub const BLACK: Word = 0;
+pub const WHITE: Word = 1;
 const CCW: Word = 0;
 const CW: Word = 1;
 
-const UP: u8 = 0;
-const RIGHT: u8 = 1;
-const DOWN: u8 = 2;
-const LEFT: u8 = 3;
+pub const UP: u8 = 0;
+pub const RIGHT: u8 = 1;
+pub const DOWN: u8 = 2;
+pub const LEFT: u8 = 3;
 
-struct HullPainter {
+/// Get the direction after turning `turn` (`CCW` or `CW`) from `dir`
+fn next_direction(dir: u8, turn: Word) -> u8 {
+    match turn {
+        // Equivalent to -1 in modulo 4, but in Rust -1 % 4 == -1, not 3
+        CCW => (dir + 3) % 4,
+        CW => (dir + 1) % 4,
+        _ => panic!(("unknown rotation", turn)),
+    }
+}
+
+/// Get the position after moving one panel from `pos` in direction `dir`
+fn step(pos: Point2D, dir: u8) -> Point2D {
+    pos + match dir {
+        UP => vector!(0, -1),
+        RIGHT => vector!(1, 0),
+        DOWN => vector!(0, 1),
+        LEFT => vector!(-1, 0),
+        _ => panic!(("unknown direction", dir)),
+    }
+}
+
+/// The hull painting robot, which can be stepped one panel at a time and inspected in between
+pub struct HullPainter {
     emulator: Emulator,
     position: Point2D,
     direction: u8,
-    hull: HashMap<Point2D, Word>,
+    hull: Canvas<Word>,
 }
 
 impl HullPainter {
-    fn from_data_file(filename: &str) -> HullPainter {
-        HullPainter {
-            emulator: Emulator::from_data_file(filename),
+    pub fn from_data_file(filename: &str) -> Result<HullPainter> {
+        HullPainter::with_start_color(filename, BLACK)
+    }
+
+    /// Create a painter starting on a panel of colour `color`, `BLACK` or `WHITE`
+    pub fn with_start_color(filename: &str, color: Word) -> Result<HullPainter> {
+        let mut hull = Canvas::new();
+        hull.set(point!(0, 0), color);
+        Ok(HullPainter {
+            emulator: Emulator::from_data_file(filename)?,
             position: point!(0, 0),
             direction: UP,
-            hull: HashMap::new(),
-        }
+            hull,
+        })
     }
 
     fn rotate(&mut self, direction: Word) {
-        self.direction = match direction {
-            // Equivalent to -1 in modulo 4, but in Rust -1 % 4 == -1, not 3
-            CCW => (self.direction + 3) % 4,
-            CW => (self.direction + 1) % 4,
-            _ => panic!(("unknown rotation", direction)),
-        }
+        self.direction = next_direction(self.direction, direction);
     }
 
     fn travel(&mut self) {
-        self.position = self.position + match self.direction {
-            UP => vector!(0, -1),
-            RIGHT => vector!(1, 0),
-            DOWN => vector!(0, 1),
-            LEFT => vector!(-1, 0),
-            _ => panic!(("unknown direction", self.direction)),
-        }
+        self.position = step(self.position, self.direction);
+    }
+
+    /// Get the panel the robot is currently on
+    pub fn position(&self) -> Point2D {
+        self.position
+    }
+
+    /// Get the direction the robot is facing, one of `UP`, `RIGHT`, `DOWN` or `LEFT`
+    pub fn direction(&self) -> u8 {
+        self.direction
     }
 
-    fn run(&mut self) {
-        loop {
-            self.emulator.write(self.hull.get(&self.position).cloned().unwrap_or(0));
-            let state = self.emulator.run();
-            let output = self.emulator.read_all();
-            if output.len() == 2 {
-                self.hull.insert(self.position, output[0]);
+    /// Run the program until the robot has painted one panel and moved, returning
+    /// `State::Continue`, or until the program stops without doing so
+    pub fn advance(&mut self) -> State {
+        self.emulator.write(self.hull.get(&self.position).cloned().unwrap_or(BLACK));
+        match self.emulator.run_until_output(2) {
+            State::Continue => {
+                let output = self.emulator.read_all();
+                self.hull.set(self.position, output[0]);
                 self.rotate(output[1]);
                 self.travel();
-            } else {
-                panic!("expected emulator output");
-            }
-            if state == State::Halt {
-                break;
-            }
+                State::Continue
+            },
+            state => {
+                assert_eq!(self.emulator.output_len(), 0, "expected color and turn from emulator");
+                state
+            },
         }
     }
 
-    fn count_painted(&self) -> usize {
+    pub fn run(&mut self) {
+        while self.advance() == State::Continue {}
+    }
+
+    pub fn count_painted(&self) -> usize {
         self.hull.len()
     }
 
+    /// Count the panels that were painted (or started) `(white, black)`
+    pub fn painted_counts(&self) -> (usize, usize) {
+        let white = self.hull.values().filter(|&&paint| paint == WHITE).count();
+        (white, self.hull.len() - white)
+    }
+
+    /// Count the panels that are currently white
+    pub fn tiles_painted_white(&self) -> usize {
+        self.painted_counts().0
+    }
+
+    /// Get the position of every white panel, sorted top-to-bottom, left-to-right
+    pub fn white_cells(&self) -> Vec<Point2D> {
+        let mut cells: Vec<Point2D> = self.hull.iter().filter(|(_, &paint)| paint == WHITE).map(|(p, _)| *p).collect();
+        cells.sort_by_key(|p| (p.y, p.x));
+        cells
+    }
+
     fn snapshot(&self) -> Vec<String> {
-        // Get the bounding box
-        let mut top_left = point!(0, 0);
-        let mut bottom_right = point!(0, 0);
-        for p in self.hull.keys() {
-            top_left.x = min(top_left.x, p.x);
-            top_left.y = min(top_left.y, p.y);
-            bottom_right.x = max(bottom_right.x, p.x);
-            bottom_right.y = max(bottom_right.y, p.y);
-        }
-        // Iterate over the bounding box to build up strings from the hull paint
-        (top_left.y ..= bottom_right.y)
-            .map(|y| {
-                (top_left.x ..= bottom_right.x)
-                    .map(|x| {
-                        if let Some(&WHITE) = self.hull.get(&point!(x, y)) {
-                            'X'
-                        } else {
-                            ' '
-                        }
-                    }).collect()
-            }).collect()
+        self.hull.render(|paint| if let Some(&WHITE) = paint { 'X' } else { ' ' })
     }
 }
 
-pub fn part1() -> usize {
-    let mut robot = HullPainter::from_data_file("day11_input.txt");
+pub fn solve_part1(filename: &str) -> Result<usize> {
+    let mut robot = HullPainter::from_data_file(filename)?;
     robot.run();
-    robot.count_painted()
+    Ok(robot.count_painted())
 }
 
-pub fn part2() -> String {
-    let mut robot = HullPainter::from_data_file("day11_input.txt");
-    robot.hull.insert(point!(0, 0), WHITE);
+pub fn part1() -> Result<usize> {
+    solve_part1("day11_input.txt")
+}
+
+pub fn solve_part2(filename: &str) -> Result<String> {
+    let mut robot = HullPainter::with_start_color(filename, WHITE)?;
     robot.run();
-    format!("\n{}\n", robot.snapshot().join("\n"))
+    let pixels = robot.snapshot();
+    util::dump_ppm("day11", &pixels)?;
+    Ok(format!("\n{}\n", pixels.join("\n")))
+}
+
+pub fn part2() -> Result<String> {
+    solve_part2("day11_input.txt")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_next_direction() {
+        assert_eq!(next_direction(UP, CW), RIGHT);
+        assert_eq!(next_direction(UP, CCW), LEFT);
+        assert_eq!(next_direction(LEFT, CW), UP);
+        assert_eq!(next_direction(DOWN, CCW), RIGHT);
+        assert_eq!((0 .. 4).fold(UP, |d, _| next_direction(d, CW)), UP);
+    }
+
+    #[test]
+    fn test_step_sequence() {
+        // The turns output by the example program in the puzzle
+        let turns = [CCW, CCW, CCW, CCW, CW, CCW, CCW];
+        let mut pos = point!(0, 0);
+        let mut dir = UP;
+        let mut positions: Vec<Point2D> = Vec::new();
+        for &turn in turns.iter() {
+            dir = next_direction(dir, turn);
+            pos = step(pos, dir);
+            positions.push(pos);
+        }
+        assert_eq!(positions, vec![
+            point!(-1, 0), point!(-1, 1), point!(0, 1), point!(0, 0),
+            point!(1, 0), point!(1, -1), point!(0, -1),
+        ]);
+        assert_eq!(dir, LEFT);
+    }
+
+    #[test]
+    fn test_advance() {
+        let mut robot = HullPainter::from_data_file("day11_input.txt").unwrap();
+        assert_eq!(robot.advance(), State::Continue);
+        // Turned from facing up, then moved one panel that way
+        assert!(robot.direction() == LEFT || robot.direction() == RIGHT);
+        assert_eq!(robot.position(), step(point!(0, 0), robot.direction()));
+        assert_eq!(robot.count_painted(), 1);
+        assert!(robot.tiles_painted_white() <= 1);
+        robot.run();
+        assert_eq!(robot.count_painted(), part1().unwrap());
+    }
+
     #[test]
     fn test_part1() {
-        assert_eq!(part1(), 2539);
+        assert_eq!(part1().unwrap(), 2539);
+    }
+
+    #[test]
+    fn test_start_color() {
+        let mut on_black = HullPainter::with_start_color("day11_input.txt", BLACK).unwrap();
+        on_black.run();
+        let mut on_white = HullPainter::with_start_color("day11_input.txt", WHITE).unwrap();
+        on_white.run();
+        assert_eq!(on_black.count_painted(), 2539);
+        assert!(on_white.count_painted() < on_black.count_painted());
+    }
+
+    #[test]
+    fn test_white_cells() {
+        let mut robot = HullPainter::with_start_color("day11_input.txt", WHITE).unwrap();
+        robot.run();
+        let (white, black) = robot.painted_counts();
+        assert_eq!(white + black, robot.count_painted());
+        let cells = robot.white_cells();
+        assert_eq!(cells.len(), white);
+        let snapshot = robot.snapshot();
+        assert_eq!(snapshot.iter().map(|row| row.matches('X').count()).sum::<usize>(), white);
+        let bbox = robot.hull.bbox().unwrap();
+        for p in cells {
+            let row = &snapshot[(p.y - bbox.min.y) as usize];
+            assert_eq!(row.as_bytes()[(p.x - bbox.min.x) as usize], b'X');
+        }
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(), format!("\n{}\n", vec![
+        assert_eq!(part2().unwrap(), format!("\n{}\n", vec![
             " XXXX X    XXXX XXX  X  X   XX XXX   XX    ",
             "    X X    X    X  X X X     X X  X X  X   ",
             "   X  X    XXX  XXX  XX      X X  X X  X   ",
@@ -130,4 +236,11 @@ mod tests {
             " XXXX XXXX XXXX XXX  X  X  XX  X  X X  X   ",
         ].join("\n")));
     }
+
+    #[test]
+    fn test_part2_ocr() {
+        let mut robot = HullPainter::with_start_color("day11_input.txt", WHITE).unwrap();
+        robot.run();
+        assert_eq!(crate::util::ocr_letters(&robot.snapshot()), "ZLEBKJRA");
+    }
 }
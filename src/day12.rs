@@ -1,13 +1,41 @@
-use std::collections::HashMap;
-extern crate num;
-use num::Integer;
-use crate::util::{Vector3D, Point3D, read_lines};
+use std::thread;
+use crate::util::{self, CycleDetector, Vector2D, Vector3D, Point2D, Point3D, read_lines};
 
 /// The state of the system, i.e. the state of every moon
 type State = Vec<Moon>;
-/// The state of the system in only one axis, see Moon::x() etc.
+/// The state of the system in only one axis, see substate()
 type SubState = Vec<(i32, i32)>;
 
+/// One of the axes of 3D space
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+enum Axis3D {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis3D {
+    const ALL: [Axis3D; 3] = [Axis3D::X, Axis3D::Y, Axis3D::Z];
+
+    /// Get the component of `v` in this axis
+    fn of(self, v: &Vector3D) -> i32 {
+        match self {
+            Axis3D::X => v.x,
+            Axis3D::Y => v.y,
+            Axis3D::Z => v.z,
+        }
+    }
+
+    /// Get a mutable reference to the component of `v` in this axis
+    fn of_mut(self, v: &mut Vector3D) -> &mut i32 {
+        match self {
+            Axis3D::X => &mut v.x,
+            Axis3D::Y => &mut v.y,
+            Axis3D::Z => &mut v.z,
+        }
+    }
+}
+
 /// Parse a single `<x=X, y=Y, z=Z>` point
 fn parse_point3d(input: &str) -> Point3D {
     let parts: Vec<&str> = input[1 .. input.len()-1].split(", ").collect();
@@ -20,106 +48,180 @@ fn parse_point3d(input: &str) -> Point3D {
 
 /// Read file as a sequence of points
 fn parse_input_points(filename: &str) -> Vec<Point3D> {
-    read_lines(filename).into_iter().map(|x| parse_point3d(x.as_str())).collect()
+    read_lines(filename).unwrap().into_iter().map(|x| parse_point3d(x.as_str())).collect()
 }
 
 /// Read file as a sequence of moons (i.e. system state) with velocity of 0
-fn read_input(filename: &str) -> State {
+pub fn read_input(filename: &str) -> State {
     parse_input_points(filename).into_iter().map(|p| Moon{position: p, velocity: vector!(0, 0, 0)}).collect()
 }
 
 #[derive(Clone,Debug,Eq,PartialEq,Hash)]
-struct Moon {
-    position: Point3D,
-    velocity: Vector3D,
+pub struct Moon {
+    pub position: Point3D,
+    pub velocity: Vector3D,
 }
 
 impl Moon {
+    /// Get the potential energy, the sum of the absolute position values
+    fn potential_energy(&self) -> i32 {
+        self.position.manhattan_length()
+    }
+
+    /// Get the kinetic energy, the sum of the absolute velocity values
+    fn kinetic_energy(&self) -> i32 {
+        self.velocity.manhattan_length()
+    }
+
+    /// Get the total energy, the product of potential and kinetic energy
     fn energy(&self) -> i32 {
+        self.potential_energy() * self.kinetic_energy()
+    }
+
+    /// Get state in a single axis as `(position, velocity)`
+    fn axis(&self, axis: Axis3D) -> (i32, i32) {
+        (axis.of(&self.position), axis.of(&self.velocity))
+    }
+}
+
+/// Get the state of the system in a single axis
+fn substate(moons: &[Moon], axis: Axis3D) -> SubState {
+    moons.iter().map(|m| m.axis(axis)).collect()
+}
+
+/// Simulate the system by one step, given the state of the system in a single axis
+fn simulate_substate(data: &mut SubState) {
+    // Update velocities
+    for i in 0 .. data.len() {
+        for j in i + 1 .. data.len() {
+            let dv = (data[j].0 - data[i].0).signum();
+            data[i].1 += dv;
+            data[j].1 -= dv;
+        }
+    }
+    // Update positions from velocities
+    for m in data.iter_mut() {
+        m.0 += m.1;
+    }
+}
+
+/// Find cycle length of system state in a single axis
+fn find_axis_cycle(moons: &[Moon], axis: Axis3D) -> usize {
+    find_substate_cycle(substate(moons, axis))
+}
+
+/// Find cycle length of the system state in every axis, each axis simulated in its own thread
+pub fn find_axis_cycles_parallel(moons: &[Moon]) -> Vec<usize> {
+    let handles: Vec<thread::JoinHandle<usize>> = Axis3D::ALL
+        .iter()
+        .map(|&axis| {
+            let data = substate(moons, axis);
+            thread::spawn(move || find_substate_cycle(data))
+        })
+        .collect();
+    handles.into_iter().map(|h| h.join().unwrap()).collect()
+}
+
+/// Find cycle length of the system state in every axis, one axis after another
+pub fn find_axis_cycles_sequential(moons: &[Moon]) -> Vec<usize> {
+    Axis3D::ALL.iter().map(|&axis| find_axis_cycle(moons, axis)).collect()
+}
+
+/// Find cycle length of a single axis of system state, starting from `data`
+fn find_substate_cycle(data: SubState) -> usize {
+    let step = |data: &SubState| {
+        let mut next = data.clone();
+        simulate_substate(&mut next);
+        next
+    };
+    CycleDetector::new(data, step).find_cycle(usize::MAX).unwrap().1
+}
+
+fn simulate_step(moons: &mut State) {
+    for &axis in Axis3D::ALL.iter() {
+        let mut data = substate(moons, axis);
+        simulate_substate(&mut data);
+        for (m, (position, velocity)) in moons.iter_mut().zip(data) {
+            *axis.of_mut(&mut m.position) = position;
+            *axis.of_mut(&mut m.velocity) = velocity;
+        }
+    }
+}
+
+/// A moon in a 2D system, for smaller hand-built scenarios
+#[derive(Clone,Debug,Eq,PartialEq,Hash)]
+pub struct Moon2D {
+    pub position: Point2D,
+    pub velocity: Vector2D,
+}
+
+impl Moon2D {
+    /// Get the total energy, the product of potential and kinetic energy
+    pub fn energy(&self) -> i32 {
         self.position.manhattan_length() * self.velocity.manhattan_length()
     }
 
     /// Get state in the x axis as `(position, velocity)`
     fn x(&self) -> (i32, i32) {
-        return (self.position.x, self.velocity.x)
+        (self.position.x, self.velocity.x)
     }
 
     /// Get state in the y axis as `(position, velocity)`
     fn y(&self) -> (i32, i32) {
-        return (self.position.y, self.velocity.y)
-    }
-
-    /// Get state in the z axis as `(position, velocity)`
-    fn z(&self) -> (i32, i32) {
-        return (self.position.z, self.velocity.z)
+        (self.position.y, self.velocity.y)
     }
 }
 
-/// Simulate the system by one step in a specific axis only
-macro_rules! simulate_axis {
-    ($data:expr, $dim:ident) => {
-        // Update velocities
-        for i in 0 .. $data.len() {
-            for j in i + 1 .. $data.len() {
-                let dv = ($data[j].position.$dim - $data[i].position.$dim).signum();
-                $data[i].velocity.$dim += dv;
-                $data[j].velocity.$dim -= dv;
-            }
-        }
-        // Update positions from velocities
-        for m in $data.iter_mut() {
-            m.position.$dim += m.velocity.$dim;
-        }
-    };
+/// Simulate a 2D system by one step, returning the new state
+pub fn simulate_step_2d(moons: &[Moon2D]) -> Vec<Moon2D> {
+    let mut x: SubState = moons.iter().map(Moon2D::x).collect();
+    let mut y: SubState = moons.iter().map(Moon2D::y).collect();
+    simulate_substate(&mut x);
+    simulate_substate(&mut y);
+    x.into_iter()
+        .zip(y)
+        .map(|((px, vx), (py, vy))| Moon2D{position: point!(px, py), velocity: vector!(vx, vy)})
+        .collect()
 }
 
-/// Get the state of the system in a specific axis
-macro_rules! substate {
-    ($data:expr, $dim:ident) => {
-        $data.iter().map(Moon::$dim).collect()
-    }
-}
-
-/// Find cycle length of system state in a specific axis
-macro_rules! find_cycle {
-    ($data:expr, $dim:ident) => {{
-        let mut history: HashMap<SubState, usize> = HashMap::new();
-        history.insert(substate!($data, $dim), 0);
-        let mut result: usize = 0;
-        for i in 1 .. {
-            simulate_axis!($data, $dim);
-            let state = substate!($data, $dim);
-            if let Some(iteration) = history.get(&state) {
-                result = i - *iteration;
-                break;
-            } else {
-                history.insert(state, i);
-            }
-        }
-        result
-    }}
+/// Get the total energy of the system
+fn total_energy(moons: &[Moon]) -> i32 {
+    moons.iter().map(Moon::energy).sum()
 }
 
-fn simulate_step(moons: &mut State) {
-    simulate_axis!(moons, x);
-    simulate_axis!(moons, y);
-    simulate_axis!(moons, z);
+/// Get the total energy of the system before each of `steps` steps, starting with the energy of
+/// `moons` as it is now, e.g. for plotting
+pub fn energy_trace(moons: &[Moon], steps: usize) -> Vec<i32> {
+    let mut state: State = moons.to_vec();
+    let mut trace: Vec<i32> = Vec::with_capacity(steps);
+    for _ in 0 .. steps {
+        trace.push(total_energy(&state));
+        simulate_step(&mut state);
+    }
+    trace
 }
 
-pub fn part1() -> i32 {
-    let mut state = read_input("day12_input.txt");
+pub fn solve_part1(filename: &str) -> i32 {
+    let mut state = read_input(filename);
     for _ in 0 .. 1000 {
         simulate_step(&mut state);
     }
-    state.iter().map(Moon::energy).sum()
+    total_energy(&state)
+}
+
+pub fn part1() -> i32 {
+    solve_part1("day12_input.txt")
+}
+
+pub fn solve_part2(filename: &str) -> usize {
+    let state = read_input(filename);
+    let cycles = find_axis_cycles_parallel(&state);
+    let (cx, cy, cz) = (cycles[0] as i64, cycles[1] as i64, cycles[2] as i64);
+    util::lcm_all(&[cx, cy, cz]) as usize
 }
 
 pub fn part2() -> usize {
-    let mut state = read_input("day12_input.txt");
-    let cycle_x = find_cycle!(state, x);
-    let cycle_y = find_cycle!(state, y);
-    let cycle_z = find_cycle!(state, z);
-    cycle_x.lcm(&cycle_y).lcm(&cycle_z)
+    solve_part2("day12_input.txt")
 }
 
 #[cfg(test)]
@@ -185,6 +287,83 @@ mod tests {
         assert_eq!(moons.iter().map(Moon::energy).sum::<i32>(), 1940);
     }
 
+    #[test]
+    fn test_energy_trace() {
+        let moons = read_input("day12_example1.txt");
+        let trace = energy_trace(&moons, 11);
+        assert_eq!(trace.len(), 11);
+        assert_eq!(trace[0], total_energy(&moons));
+        assert_eq!(trace[10], 179);
+        // Energy isn't conserved
+        assert!(trace.iter().any(|&e| e != trace[0]));
+        assert!(energy_trace(&moons, 0).is_empty());
+    }
+
+    #[test]
+    fn test_energy_breakdown() {
+        let mut moons = read_input("day12_example2.txt");
+        for _ in 0..100 {
+            simulate_step(&mut moons);
+        }
+        assert_eq!(moons[0].potential_energy(), 8 + 12 + 9);
+        assert_eq!(moons[0].kinetic_energy(), 7 + 3);
+        assert_eq!(moons[0].energy(), 290);
+    }
+
+    #[test]
+    fn test_find_axis_cycle_example1() {
+        let moons = read_input("day12_example1.txt");
+        assert_eq!(find_axis_cycle(&moons, Axis3D::X), 18);
+        assert_eq!(find_axis_cycle(&moons, Axis3D::Y), 28);
+        assert_eq!(find_axis_cycle(&moons, Axis3D::Z), 44);
+    }
+
+    #[test]
+    fn test_substate() {
+        let mut moons = read_input("day12_example1.txt");
+        simulate_step(&mut moons);
+        assert_eq!(substate(&moons, Axis3D::X), moons.iter().map(|m| (m.position.x, m.velocity.x)).collect::<SubState>());
+        assert_eq!(substate(&moons, Axis3D::Y), moons.iter().map(|m| (m.position.y, m.velocity.y)).collect::<SubState>());
+        assert_eq!(substate(&moons, Axis3D::Z), moons.iter().map(|m| (m.position.z, m.velocity.z)).collect::<SubState>());
+        assert_eq!(substate(&moons, Axis3D::X), vec![(2, 3), (3, 1), (1, -3), (2, -1)]);
+    }
+
+    #[test]
+    fn test_find_axis_cycles_parallel() {
+        for filename in ["day12_example1.txt", "day12_example2.txt", "day12_input.txt"].iter() {
+            let moons = read_input(filename);
+            assert_eq!(find_axis_cycles_parallel(&moons), find_axis_cycles_sequential(&moons));
+        }
+    }
+
+    #[test]
+    fn test_simulate_step_2d() {
+        let initial = vec![
+            Moon2D{position: point!(0, 0), velocity: vector!(0, 0)},
+            Moon2D{position: point!(3, 0), velocity: vector!(0, 0)},
+        ];
+        // Moons attract each other
+        let mut moons = simulate_step_2d(&initial);
+        assert_eq!(moons, vec![
+            Moon2D{position: point!(1, 0), velocity: vector!(1, 0)},
+            Moon2D{position: point!(2, 0), velocity: vector!(-1, 0)},
+        ]);
+        // Moons pass through each other, come to a stop, and then fall back, with velocities
+        // always equal and opposite; a separation of 3 has a period of 8
+        for step in 2 ..= 8 {
+            moons = simulate_step_2d(&moons);
+            assert_eq!(moons[0].velocity, vector!(0, 0) - moons[1].velocity);
+            assert_eq!(moons == initial, step == 8);
+        }
+        assert_eq!(moons.iter().map(Moon2D::energy).sum::<i32>(), 0);
+    }
+
+    #[test]
+    fn test_solve_part2_examples() {
+        assert_eq!(solve_part2("day12_example1.txt"), 2772);
+        assert_eq!(solve_part2("day12_example2.txt"), 4686774924);
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(), 7687);
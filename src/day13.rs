@@ -1,9 +1,11 @@
-use crate::intcode::*;
 use std::collections::HashMap;
-use crate::util::Point2D;
-use std::cmp::{min, max};
+use std::fs;
+use std::path::Path;
+use crate::error::Result;
+use crate::intcode::*;
+use crate::util::{BoundingBox2D, Canvas, Grid2D, Point2D, Vector2D};
 
-#[derive(Debug,Eq,PartialEq)]
+#[derive(Clone,Copy,Debug,Eq,Hash,PartialEq)]
 enum Tile {
     Empty,
     Wall,
@@ -38,9 +40,7 @@ impl From<Tile> for char {
 }
 
 struct Display {
-    data: HashMap<Point2D, Tile>,
-    top_left: Point2D,
-    bottom_right: Point2D,
+    canvas: Canvas<Tile>,
     paddle: Option<Point2D>,
     ball: Option<Point2D>,
 }
@@ -48,74 +48,132 @@ struct Display {
 impl Display {
     fn new() -> Display {
         Display {
-            data: HashMap::new(),
-            top_left: point!(0, 0),
-            bottom_right: point!(0, 0),
+            canvas: Canvas::new(),
             paddle: None,
             ball: None,
         }
     }
 
     fn draw(&mut self, x: i32, y: i32, tile: Tile) {
-        self.top_left.x = min(self.top_left.x, x);
-        self.top_left.y = min(self.top_left.y, y);
-        self.bottom_right.x = max(self.bottom_right.x, x);
-        self.bottom_right.y = max(self.bottom_right.y, y);
-        if tile == Tile::Empty {
-            self.data.remove(&point!(x, y));
-        } else {
-            if tile == Tile::Paddle {
-                self.paddle = Some(point!(x, y));
-            } else if tile == Tile::Ball {
-                self.ball = Some(point!(x, y));
-            }
-            self.data.insert(point!(x, y), tile);
+        if tile == Tile::Paddle {
+            self.paddle = Some(point!(x, y));
+        } else if tile == Tile::Ball {
+            self.ball = Some(point!(x, y));
         }
+        self.canvas.set(point!(x, y), tile);
+    }
+
+    /// Count how many of each tile type is on the display
+    fn count_tiles(&self) -> HashMap<Tile, usize> {
+        let mut counts: HashMap<Tile, usize> = HashMap::new();
+        for tile in self.canvas.values() {
+            *counts.entry(*tile).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    fn block_count(&self) -> usize {
+        self.count_tiles().get(&Tile::Block).cloned().unwrap_or(0)
+    }
+
+    /// Get the current frame as a dense grid covering everything drawn, filling any gaps with
+    /// `Tile::Empty`
+    ///
+    /// If nothing has been drawn, this is a single empty tile at `(0, 0)`.
+    #[allow(dead_code)]
+    fn to_grid(&self) -> Grid2D<Tile> {
+        let bbox = self.canvas.bbox().cloned().unwrap_or_else(|| BoundingBox2D::new(&point!(0, 0)));
+        let width = (bbox.max.x - bbox.min.x + 1) as usize;
+        let height = (bbox.max.y - bbox.min.y + 1) as usize;
+        let data = bbox.iter().map(|p| self.canvas.get(&p).cloned().unwrap_or(Tile::Empty)).collect();
+        Grid2D { data, width, height, bbox }
+    }
+
+    /// Render the display as rows of characters
+    fn render(&self) -> Vec<String> {
+        self.canvas.render(|tile| char::from(*tile.unwrap_or(&Tile::Empty)))
     }
 
     #[allow(dead_code)]
     fn print(&self) {
-        for y in self.top_left.y ..= self.bottom_right.y {
-            for x in self.top_left.x ..= self.bottom_right.x {
-                print!("{}", match self.data.get(&point!(x, y)).unwrap_or(&Tile::Empty) {
-                    Tile::Empty => ' ',
-                    Tile::Wall => '#',
-                    Tile::Block => 'X',
-                    Tile::Paddle => '=',
-                    Tile::Ball => 'o',
-                });
-            }
-            println!();
+        for row in self.render() {
+            println!("{}", row);
         }
     }
 }
 
+/// The outcome of running the game for a step
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+enum GameState {
+    /// Still running, waiting for the next joystick input
+    Playing,
+    /// Halted with every block broken
+    Won,
+    /// Halted with blocks remaining, i.e. the ball got past the paddle
+    Lost,
+}
+
 struct ArcadeMachine {
     emulator: Emulator,
     display: Display,
     score: Word,
+    /// Rendered frames, if recording, see `start_recording()`
+    frames: Option<Vec<Vec<String>>>,
 }
 
 impl ArcadeMachine {
-    fn from_data_file(filename: &str) -> ArcadeMachine {
-        ArcadeMachine {
-            emulator: Emulator::from_data_file(filename),
+    fn from_data_file(filename: &str) -> Result<ArcadeMachine> {
+        Ok(ArcadeMachine {
+            emulator: Emulator::from_data_file(filename)?,
             display: Display::new(),
             score: 0,
-        }
+            frames: None,
+        })
     }
 
     fn insert_coin(&mut self) {
         self.emulator.set(0, 2);
     }
 
+    /// Record the score and display after every step from now on
+    fn start_recording(&mut self) {
+        self.frames.get_or_insert_with(Vec::new);
+    }
+
+    /// Get the recorded frames, each a score line followed by the rows of the display
+    fn frames(&self) -> &[Vec<String>] {
+        self.frames.as_deref().unwrap_or(&[])
+    }
+
+    /// Concatenate the recorded frames, each followed by a `fps_marker` line
+    fn replay_to_string(&self, fps_marker: &str) -> String {
+        let mut out = String::new();
+        for frame in self.frames() {
+            for row in frame {
+                out.push_str(row);
+                out.push('\n');
+            }
+            out.push_str(fps_marker);
+            out.push('\n');
+        }
+        out
+    }
+
     #[allow(dead_code)]
     fn print(&self) {
         println!("Score: {}", self.score);
         self.display.print();
     }
 
-    fn step(&mut self, strategy: fn(&ArcadeMachine) -> Word) -> bool {
+    fn is_won(&self) -> bool {
+        self.emulator.next_op() == Op::Halt && self.display.block_count() == 0
+    }
+
+    fn step(&mut self, strategy: &mut dyn PaddleStrategy) -> GameState {
+        if self.emulator.needs_input() {
+            let input = strategy.decide(self);
+            self.emulator.write(input);
+        }
         let state = self.emulator.run();
         for chunk in self.emulator.read_all().chunks(3) {
             if (chunk[0], chunk[1]) == (-1, 0) {
@@ -124,31 +182,102 @@ impl ArcadeMachine {
                 self.display.draw(chunk[0] as i32, chunk[1] as i32, From::from(chunk[2]));
             }
         }
-        match state {
-            State::Continue => {},
-            State::ReadWait => {
-                self.emulator.write(strategy(&self));
-            },
-            State::Halt => {
-                return false;
-            }
+        if let Some(frames) = self.frames.as_mut() {
+            let mut frame = vec![format!("Score: {}", self.score)];
+            frame.extend(self.display.render());
+            frames.push(frame);
+        }
+        if state != State::Halt {
+            GameState::Playing
+        } else if self.is_won() {
+            GameState::Won
+        } else {
+            GameState::Lost
         }
-        return true;
     }
 
-    fn run(&mut self, strategy: fn(&ArcadeMachine) -> Word) {
-        while self.step(strategy) {}
+    /// Run the game to the end, returning whether it was won or lost
+    fn run(&mut self, strategy: &mut dyn PaddleStrategy) -> GameState {
+        loop {
+            match self.step(strategy) {
+                GameState::Playing => (),
+                result => return result,
+            }
+        }
     }
 }
 
-pub fn part1() -> usize {
-    let mut emulator = Emulator::from_data_file("day13_input.txt");
+pub fn solve_part1(filename: &str) -> Result<usize> {
+    let mut emulator = Emulator::from_data_file(filename)?;
     let mut screen = Display::new();
     emulator.run();
     for chunk in emulator.read_all().chunks(3) {
         screen.draw(chunk[0] as i32, chunk[1] as i32, From::from(chunk[2]));
     }
-    screen.data.values().map(|t| *t == Tile::Block).filter(|x| *x).count()
+    Ok(screen.block_count())
+}
+
+pub fn part1() -> Result<usize> {
+    solve_part1("day13_input.txt")
+}
+
+/// A way of choosing the joystick input, i.e. -1, 0 or 1 to move the paddle left, not at all, or right
+trait PaddleStrategy {
+    fn decide(&mut self, arcade: &ArcadeMachine) -> Word;
+}
+
+/// Any closure can be used as a strategy too
+impl<F: FnMut(&ArcadeMachine) -> Word> PaddleStrategy for F {
+    fn decide(&mut self, arcade: &ArcadeMachine) -> Word {
+        self(arcade)
+    }
+}
+
+/// Always move the paddle towards the X coordinate of the ball, see `match_ball()`
+struct FollowBall;
+
+impl PaddleStrategy for FollowBall {
+    fn decide(&mut self, arcade: &ArcadeMachine) -> Word {
+        match_ball(arcade)
+    }
+}
+
+/// Move the paddle towards the X coordinate where the ball is predicted to reach the paddle's row
+///
+/// The ball's velocity is inferred from its previous position, and the prediction accounts for
+/// bounces off the side walls (but not off blocks, so it's re-evaluated every frame).
+///
+/// This was expected to finish the game in fewer frames than `FollowBall`, but it doesn't: the
+/// ball's path doesn't depend on where it hits the paddle, so both take the same number of frames
+/// (6524 for the puzzle input). It does move the paddle less, since it waits at the landing column
+/// instead of chasing the ball there.
+#[allow(dead_code)]
+#[derive(Default)]
+struct PredictBounce {
+    previous: Option<Point2D>,
+}
+
+impl PaddleStrategy for PredictBounce {
+    fn decide(&mut self, arcade: &ArcadeMachine) -> Word {
+        let (ball, paddle) = match (arcade.display.ball, arcade.display.paddle) {
+            (Some(ball), Some(paddle)) => (ball, paddle),
+            _ => return 0,
+        };
+        let velocity = self.previous.map(|p| ball - p).unwrap_or(vector!(0, 0));
+        self.previous = Some(ball);
+        let target = if velocity.y > 0 {
+            // Reflect the straight-line prediction back into the space between the walls
+            let bbox = arcade.display.canvas.bbox().unwrap();
+            let low = bbox.min.x + 1;
+            let span = bbox.max.x - 1 - low;
+            let offset = ball.x + velocity.x * (paddle.y - 1 - ball.y) - low;
+            let folded = offset.rem_euclid(2 * span);
+            low + if folded > span { 2 * span - folded } else { folded }
+        } else {
+            ball.x
+        };
+        (target - paddle.x).signum() as Word
+    }
 }
 
 /// Always move the paddle towards the X coordinate of the ball
@@ -160,12 +289,25 @@ fn match_ball(arcade: &ArcadeMachine) -> Word {
     }
 }
 
-pub fn part2() -> Word {
-    let mut arcade = ArcadeMachine::from_data_file("day13_input.txt");
+/// Play the game, writing a replay of every frame to `<$AOC_REPLAY_DIR>/day13.txt` if
+/// `AOC_REPLAY_DIR` is set
+pub fn solve_part2(filename: &str) -> Result<Word> {
+    let mut arcade = ArcadeMachine::from_data_file(filename)?;
+    let replay_dir = std::env::var_os("AOC_REPLAY_DIR");
+    if replay_dir.is_some() {
+        arcade.start_recording();
+    }
     arcade.insert_coin();
-    arcade.run(match_ball);
+    arcade.run(&mut FollowBall);
 //    arcade.print();
-    arcade.score
+    if let Some(dir) = replay_dir {
+        fs::write(Path::new(&dir).join("day13.txt"), arcade.replay_to_string("---"))?;
+    }
+    Ok(arcade.score)
+}
+
+pub fn part2() -> Result<Word> {
+    solve_part2("day13_input.txt")
 }
 
 #[cfg(test)]
@@ -174,11 +316,154 @@ mod tests {
 
     #[test]
     fn test_part1() {
-        assert_eq!(part1(), 306);
+        assert_eq!(part1().unwrap(), 306);
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(), 15328);
+        assert_eq!(part2().unwrap(), 15328);
+    }
+
+    #[test]
+    fn test_count_tiles() {
+        let mut display = Display::new();
+        for (y, row) in ["#####", "#XX #", "#X o#", "#  =#"].iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let tile = match c {
+                    '#' => Tile::Wall,
+                    'X' => Tile::Block,
+                    '=' => Tile::Paddle,
+                    'o' => Tile::Ball,
+                    _ => Tile::Empty,
+                };
+                display.draw(x as i32, y as i32, tile);
+            }
+        }
+        // Redrawing a position replaces what was there
+        display.draw(1, 1, Tile::Empty);
+        let counts = display.count_tiles();
+        assert_eq!(counts.get(&Tile::Wall), Some(&11));
+        assert_eq!(counts.get(&Tile::Block), Some(&2));
+        assert_eq!(counts.get(&Tile::Paddle), Some(&1));
+        assert_eq!(counts.get(&Tile::Ball), Some(&1));
+        assert_eq!(counts.get(&Tile::Empty), Some(&5));
+        assert_eq!(display.block_count(), 2);
+    }
+
+    #[test]
+    fn test_to_grid() {
+        let mut display = Display::new();
+        assert_eq!(display.to_grid().data, vec![Tile::Empty]);
+        display.draw(2, 1, Tile::Wall);
+        display.draw(4, 3, Tile::Ball);
+        let grid = display.to_grid();
+        assert_eq!((grid.width, grid.height), (3, 3));
+        assert_eq!(grid.get(&point!(2, 1)), Some(&Tile::Wall));
+        assert_eq!(grid.get(&point!(4, 3)), Some(&Tile::Ball));
+        assert_eq!(grid.get(&point!(3, 2)), Some(&Tile::Empty));
+        assert_eq!(grid.data.iter().filter(|&&t| t == Tile::Empty).count(), 7);
+
+        // The initial board, drawn like part 1
+        let mut emulator = Emulator::from_data_file("day13_input.txt").unwrap();
+        let mut display = Display::new();
+        emulator.run();
+        for chunk in emulator.read_all().chunks(3) {
+            display.draw(chunk[0] as i32, chunk[1] as i32, From::from(chunk[2]));
+        }
+        let grid = display.to_grid();
+        assert_eq!(grid.data.iter().filter(|&&t| t == Tile::Block).count(), display.block_count());
+        assert_eq!(display.block_count(), part1().unwrap());
+    }
+
+    #[test]
+    fn test_game_won() {
+        let mut arcade = ArcadeMachine::from_data_file("day13_input.txt").unwrap();
+        arcade.insert_coin();
+        assert!(!arcade.is_won());
+        assert_eq!(arcade.run(&mut FollowBall), GameState::Won);
+        assert!(arcade.is_won());
+        assert_eq!(arcade.display.block_count(), 0);
+    }
+
+    #[test]
+    fn test_game_lost() {
+        let mut arcade = ArcadeMachine::from_data_file("day13_input.txt").unwrap();
+        arcade.insert_coin();
+        assert_eq!(arcade.run(&mut |_: &ArcadeMachine| 0), GameState::Lost);
+        assert!(!arcade.is_won());
+        assert!(arcade.display.block_count() > 0);
+    }
+
+    #[test]
+    fn test_capturing_strategy() {
+        let mut arcade = ArcadeMachine::from_data_file("day13_input.txt").unwrap();
+        arcade.insert_coin();
+        let mut decisions = 0;
+        let (steps, _) = count_steps(&mut arcade, &mut |a: &ArcadeMachine| {
+            decisions += 1;
+            match_ball(a)
+        });
+        // Every step but the last stops to wait for input, which the next step asks the strategy for
+        assert_eq!(decisions, steps);
+        assert_eq!(arcade.score, 15328);
+    }
+
+    #[test]
+    fn test_recording() {
+        let mut arcade = ArcadeMachine::from_data_file("day13_input.txt").unwrap();
+        arcade.insert_coin();
+        arcade.start_recording();
+        arcade.run(&mut FollowBall);
+        assert!(!arcade.frames().is_empty());
+        let last = arcade.frames().last().unwrap();
+        assert_eq!(last[0], "Score: 15328");
+        assert!(last.iter().skip(1).all(|row| !row.contains('X')));
+        let replay = arcade.replay_to_string("---");
+        assert_eq!(replay.matches("\n---\n").count(), arcade.frames().len());
+    }
+
+    #[test]
+    fn test_follow_ball() {
+        let mut arcade = ArcadeMachine::from_data_file("day13_input.txt").unwrap();
+        arcade.insert_coin();
+        let strategy: &mut dyn PaddleStrategy = &mut FollowBall;
+        assert_eq!(arcade.run(strategy), GameState::Won);
+        assert_eq!(arcade.score, 15328);
+        assert_eq!(arcade.display.block_count(), 0);
+    }
+
+    /// Play the game with `strategy`, returning the number of steps and how many of them moved
+    /// the paddle
+    fn count_steps(arcade: &mut ArcadeMachine, strategy: &mut dyn PaddleStrategy) -> (usize, usize) {
+        let mut steps = 0;
+        let mut moves = 0;
+        let mut counting = |a: &ArcadeMachine| {
+            let input = strategy.decide(a);
+            if input != 0 {
+                moves += 1;
+            }
+            input
+        };
+        while arcade.step(&mut counting) == GameState::Playing {
+            steps += 1;
+        }
+        (steps, moves)
+    }
+
+    #[test]
+    fn test_predict_bounce() {
+        let mut arcade = ArcadeMachine::from_data_file("day13_input.txt").unwrap();
+        arcade.insert_coin();
+        let (predict_steps, predict_moves) = count_steps(&mut arcade, &mut PredictBounce::default());
+        assert_eq!(arcade.score, 15328);
+        assert!(arcade.display.canvas.values().all(|t| *t != Tile::Block));
+
+        let mut arcade = ArcadeMachine::from_data_file("day13_input.txt").unwrap();
+        arcade.insert_coin();
+        let (follow_steps, follow_moves) = count_steps(&mut arcade, &mut FollowBall);
+        assert_eq!(arcade.score, 15328);
+        // The ball takes the same path either way, but the paddle doesn't chase it as much
+        assert_eq!(predict_steps, follow_steps);
+        assert!(predict_moves < follow_moves, "{} >= {}", predict_moves, follow_moves);
     }
 }
@@ -1,5 +1,4 @@
-use std::cmp::min;
-use std::collections::{HashMap};
+use std::collections::{HashMap, VecDeque};
 use std::num::ParseIntError;
 use std::str::FromStr;
 use crate::util::read_lines;
@@ -47,67 +46,67 @@ impl FromStr for Reaction {
 #[derive(Debug)]
 struct Factory {
     reactions: HashMap<String, Reaction>,
-    produced: HashMap<String, i64>,
-    surplus: HashMap<String, i64>,
 }
 
 impl Factory {
     fn from_data_file(filename: &str) -> Factory {
-        let reactions: HashMap<String, Reaction> = read_lines(filename)
+        let reactions: HashMap<String, Reaction> = read_lines(filename).unwrap()
             .iter()
             .map(|x| x.parse::<Reaction>().unwrap())
             .map(|x| (x.output.name.clone(), x))
             .collect();
-        let mut f = Factory {
-            reactions,
-            produced: HashMap::new(),
-            surplus: HashMap::new(),
-        };
-        for name in f.reactions.keys() {
-            f.produced.insert(name.clone(), 0);
-            f.surplus.insert(name.clone(), 0);
-        }
-        return f;
+        Factory { reactions }
     }
 
-    /// Consume at most `amount` of `name` from surplus only, returning the amount still required
-    fn consume_surplus(&mut self, name: &String, amount: i64) -> i64 {
-        let surplus = self.surplus.entry(name.clone()).or_insert(0);
-        let consumed = min(amount, *surplus);
-        *surplus -= consumed;
-        return amount - consumed;
-    }
-
-    /// Consume `amount` of `name`, producing more if necessary
-    fn consume(&mut self, name: &String, amount: i64) {
-        let amount = self.consume_surplus(name, amount);
-        if amount != 0 {
-            self.produce(name, amount);
+    /// Order chemicals such that each chemical comes before every chemical used to produce it
+    ///
+    /// Uses Kahn's algorithm: a chemical is only ready once every reaction consuming it has been
+    /// ordered, so by the time it's reached the total amount required of it is known.
+    fn production_order(&self) -> Vec<String> {
+        let mut consumers: HashMap<&str, usize> = HashMap::new();
+        for reaction in self.reactions.values() {
+            consumers.entry(&reaction.output.name).or_insert(0);
+            for input in reaction.inputs.iter() {
+                *consumers.entry(&input.name).or_insert(0) += 1;
+            }
+        }
+        let mut ready: VecDeque<&str> = consumers.iter().filter(|(_, &n)| n == 0).map(|(&k, _)| k).collect();
+        let mut order: Vec<String> = Vec::new();
+        while let Some(name) = ready.pop_front() {
+            order.push(name.to_string());
+            for input in self.reactions.get(name).iter().flat_map(|r| r.inputs.iter()) {
+                let n = consumers.get_mut(input.name.as_str()).unwrap();
+                *n -= 1;
+                if *n == 0 {
+                    ready.push_back(&input.name);
+                }
+            }
         }
+        assert_eq!(order.len(), consumers.len(), "reactions contain a cycle");
+        order
     }
 
-    /// Produce `amount` of `name`, saving any surplus amount
-    fn produce(&mut self, name: &String, amount: i64) {
-        if let Some(reaction) = self.reactions.get(name) {
-            let reaction = reaction.clone();
-            let count = div_ceil(amount, reaction.output.amount);
-            for input in reaction.inputs.iter() {
-                self.consume(&input.name, input.amount * count);
-            }
-            let produced = count * reaction.output.amount;
-            *self.produced.entry(name.clone()).or_insert(0) += produced;
-            *self.surplus.entry(name.clone()).or_insert(0) += produced - amount;
-        } else {
+    /// Calculate the amount of every chemical consumed or produced to make `amount` of `name`
+    fn requirements(&self, name: &str, amount: i64) -> HashMap<String, i64> {
+        let mut required: HashMap<String, i64> = HashMap::new();
+        required.insert(name.to_string(), amount);
+        for chemical in self.production_order() {
+            let amount = *required.get(&chemical).unwrap_or(&0);
             // ORE doesn't have a reaction to produce it, so just assume it exists
-            *self.produced.entry(name.clone()).or_insert(0) += amount;
+            if let Some(reaction) = self.reactions.get(&chemical) {
+                let count = div_ceil(amount, reaction.output.amount);
+                for input in reaction.inputs.iter() {
+                    *required.entry(input.name.clone()).or_insert(0) += input.amount * count;
+                }
+            }
         }
+        required
     }
 }
 
 fn ore_required(filename: &str, fuel: i64) -> i64 {
-    let mut factory = Factory::from_data_file(filename);
-    factory.produce(&"FUEL".to_string(), fuel);
-    *factory.produced.get("ORE").unwrap()
+    let factory = Factory::from_data_file(filename);
+    factory.requirements("FUEL", fuel)["ORE"]
 }
 
 fn max_fuel_production(filename: &str) -> i64 {
@@ -125,12 +124,20 @@ fn max_fuel_production(filename: &str) -> i64 {
     current
 }
 
+pub fn solve_part1(filename: &str) -> i64 {
+    ore_required(filename, 1)
+}
+
 pub fn part1() -> i64 {
-    ore_required("day14_input.txt", 1)
+    solve_part1("day14_input.txt")
+}
+
+pub fn solve_part2(filename: &str) -> i64 {
+    max_fuel_production(filename)
 }
 
 pub fn part2() -> i64 {
-    max_fuel_production("day14_input.txt")
+    solve_part2("day14_input.txt")
 }
 
 #[cfg(test)]
@@ -146,6 +153,25 @@ mod tests {
         assert_eq!(ore_required("day14_example5.txt", 1), 2210736);
     }
 
+    #[test]
+    fn test_production_order() {
+        // A is used both directly and via B, C and D, so must come after all of them
+        let reactions = [
+            "3 ORE => 2 A",
+            "1 A => 1 B",
+            "2 A, 1 B => 1 C",
+            "1 B, 1 C => 1 D",
+            "1 A, 1 C, 1 D => 1 FUEL",
+        ];
+        let factory = Factory {
+            reactions: reactions.iter()
+                .map(|x| x.parse::<Reaction>().unwrap())
+                .map(|x| (x.output.name.clone(), x))
+                .collect(),
+        };
+        assert_eq!(factory.production_order(), vec!["FUEL", "D", "C", "B", "A", "ORE"]);
+    }
+
     #[test]
     fn test_max_fuel_production() {
         assert_eq!(max_fuel_production("day14_example3.txt"), 82892753);
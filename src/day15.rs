@@ -1,9 +1,8 @@
-use std::cmp::max;
 use std::collections::{HashMap, VecDeque};
 use crate::intcode::*;
-use crate::util::{Vector2D, Point2D, BoundingBox2D};
+use crate::util::{flood_fill, Connectivity, Vector2D, Point2D, BoundingBox2D};
 
-#[derive(Copy,Clone,Debug)]
+#[derive(Copy,Clone,Debug,Eq,PartialEq)]
 enum Direction {
     North = 1,
     South = 2,
@@ -11,6 +10,25 @@ enum Direction {
     East = 4,
 }
 
+impl Direction {
+    fn turn_right(self) -> Direction {
+        match self {
+            Direction::North => Direction::East,
+            Direction::East => Direction::South,
+            Direction::South => Direction::West,
+            Direction::West => Direction::North,
+        }
+    }
+
+    fn turn_left(self) -> Direction {
+        self.turn_right().turn_right().turn_right()
+    }
+
+    fn reverse(self) -> Direction {
+        self.turn_right().turn_right()
+    }
+}
+
 impl From<Direction> for Word {
     fn from(d: Direction) -> Self {
         d as Word
@@ -80,7 +98,7 @@ struct Droid {
 impl Droid {
     fn from_data_file(filename: &str) -> Droid {
         Droid {
-            emulator: Emulator::from_data_file(filename),
+            emulator: Emulator::from_data_file(filename).unwrap(),
             map: HashMap::new(),
             oxygen: None,
         }
@@ -120,71 +138,213 @@ impl Droid {
         }
     }
 
-    /// Use flood fill to replace floor with oxygen
-    fn flow_oxygen(&mut self) -> usize {
-        let directions = [Direction::North, Direction::South, Direction::West, Direction::East];
-        let mut furthest: usize = 0;
-        let mut queue: VecDeque<(Point2D, usize)> = VecDeque::new();
-        queue.push_back((self.oxygen.unwrap().0, 0));
-
-        while let Some((p, distance)) = queue.pop_front() {
-            furthest = max(furthest, distance);
-            for d in directions.iter().cloned() {
-                let next_position = p + From::from(d);
-                if let Some(state) = self.map.get_mut(&next_position) {
-                    // Only process tiles that are floor
-                    if state.tile != Tile::Floor {
-                        continue;
-                    }
-                    state.tile = Tile::Oxygen;
-                    queue.push_back((next_position, distance + 1));
+    /// Explore the map with a single emulator by following the right-hand wall
+    ///
+    /// `discover_map` keeps a clone of the emulator for every tile it discovers, so memory grows
+    /// with the size of the maze (roughly 1600 copies of the program's memory for the real input).
+    /// This drives the one emulator around, backtracking out of dead ends, so only the tile map
+    /// grows. The maze is a tree, so the walk visits every tile and ends when it's about to repeat
+    /// its first move.
+    fn discover_map_wall_follower(&self) -> HashMap<Point2D, Tile> {
+        let mut emulator = self.emulator.clone();
+        let mut map: HashMap<Point2D, Tile> = HashMap::new();
+        let start = point!(0, 0);
+        map.insert(start, Tile::Floor);
+        let mut position = start;
+        let mut facing = Direction::North;
+        let mut first_move: Option<Direction> = None;
+        loop {
+            let candidates = [facing.turn_right(), facing, facing.turn_left(), facing.reverse()];
+            let mut moved = false;
+            for d in candidates.iter().cloned() {
+                let next_position = position + From::from(d);
+                // Don't bump into walls we already know about
+                if map.get(&next_position) == Some(&Tile::Wall) {
+                    continue;
+                }
+                if position == start && first_move == Some(d) {
+                    return map;
                 }
+                emulator.write(From::from(d));
+                emulator.run();
+                let tile: Tile = From::from(emulator.read().unwrap());
+                map.insert(next_position, tile);
+                if tile != Tile::Wall {
+                    first_move.get_or_insert(d);
+                    position = next_position;
+                    facing = d;
+                    moved = true;
+                    break;
+                }
+            }
+            if !moved {
+                // Walled in on all sides, so there's nothing more to find
+                return map;
             }
         }
+    }
+
+    /// Check if exploration is finished, i.e. there's nothing unknown next to any open tile
+    #[allow(dead_code)]
+    fn is_fully_explored(&self) -> bool {
+        !self.map.is_empty() && self.map.iter()
+            .filter(|(_, state)| state.tile != Tile::Wall)
+            .all(|(p, _)| Connectivity::Four.neighbours(*p).all(|n| self.map.contains_key(&n)))
+    }
+
+    /// Find the shortest distance between two points, moving only through discovered floor
+    ///
+    /// Returns `None` if either point isn't floor (or the oxygen system) or they're not connected.
+    fn shortest_path(&self, from: &Point2D, to: &Point2D) -> Option<usize> {
+        if !self.is_open(from) {
+            return None;
+        }
+        flood_fill(*from, Connectivity::Four, |p| self.is_open(p)).get(to).cloned()
+    }
+
+    /// Check if `p` has been discovered to be floor (or the oxygen system)
+    fn is_open(&self, p: &Point2D) -> bool {
+        matches!(self.map.get(p), Some(state) if matches!(state.tile, Tile::Floor | Tile::Oxygen))
+    }
 
-        return furthest;
+    /// Count the open neighbours of `p`
+    fn degree(&self, p: &Point2D) -> usize {
+        Connectivity::Four.neighbours(*p).filter(|n| self.is_open(n)).count()
+    }
+
+    /// Get the open tiles matching `f(degree)`, sorted top-to-bottom, left-to-right
+    fn open_tiles_by_degree(&self, f: impl Fn(usize) -> bool) -> Vec<Point2D> {
+        let mut tiles: Vec<Point2D> = self.map.keys().filter(|p| self.is_open(p) && f(self.degree(p))).cloned().collect();
+        tiles.sort_by_key(|p| (p.y, p.x));
+        tiles
+    }
+
+    /// Get the open tiles with only one open neighbour
+    #[allow(dead_code)]
+    fn dead_ends(&self) -> Vec<Point2D> {
+        self.open_tiles_by_degree(|d| d == 1)
+    }
+
+    /// Get the open tiles with three or more open neighbours
+    #[allow(dead_code)]
+    fn junctions(&self) -> Vec<Point2D> {
+        self.open_tiles_by_degree(|d| d >= 3)
     }
 
     #[allow(dead_code)]
     fn print_map(&self) {
-        let mut bbox = BoundingBox2D::new(&point!(0, 0));
-        for p in self.map.keys() {
-            bbox.include(p);
-        }
-        for p in bbox.iter() {
-            let tile = self.map.get(&p).map(|state| state.tile).unwrap_or(Tile::Empty);
-            print!("{}", match tile {
-                Tile::Empty => ' ',
-                Tile::Wall => '#',
-                Tile::Floor => '.',
-                Tile::Oxygen => 'O',
-            });
-            if p.x == bbox.max.x {
-                println!();
+        let bbox = BoundingBox2D::from_points(self.map.keys().cloned()).unwrap();
+        for row in bbox.iter_rows() {
+            for p in row {
+                let tile = self.map.get(&p).map(|state| state.tile).unwrap_or(Tile::Empty);
+                print!("{}", match tile {
+                    Tile::Empty => ' ',
+                    Tile::Wall => '#',
+                    Tile::Floor => '.',
+                    Tile::Oxygen => 'O',
+                });
             }
+            println!();
         }
         println!("Oxygen system @ {:?}", self.oxygen);
     }
 }
 
-pub fn part1() -> usize {
-    let mut droid = Droid::from_data_file("day15_input.txt");
+/// Use flood fill to find how long oxygen takes to reach all the floor of `map`
+fn flow_oxygen(map: &HashMap<Point2D, Tile>) -> usize {
+    let oxygen = map.iter().find(|(_, &tile)| tile == Tile::Oxygen).map(|(p, _)| *p).unwrap();
+    let is_floor = |p: &Point2D| map.get(p) == Some(&Tile::Floor);
+    flood_fill(oxygen, Connectivity::Four, is_floor)
+        .values()
+        .cloned()
+        .max()
+        .unwrap_or(0)
+}
+
+pub fn solve_part1(filename: &str) -> usize {
+    let mut droid = Droid::from_data_file(filename);
     droid.discover_map();
 //    droid.print_map();
-    droid.oxygen.unwrap().1
+    let (oxygen, _) = droid.oxygen.unwrap();
+    droid.shortest_path(&point!(0, 0), &oxygen).unwrap()
+}
+
+pub fn part1() -> usize {
+    solve_part1("day15_input.txt")
+}
+
+pub fn solve_part2(filename: &str) -> usize {
+    // Only the layout is needed, not the distance to each tile, so explore with a single emulator
+    let droid = Droid::from_data_file(filename);
+    flow_oxygen(&droid.discover_map_wall_follower())
 }
 
 pub fn part2() -> usize {
-    let mut droid = Droid::from_data_file("day15_input.txt");
-    droid.discover_map();
-//    droid.print_map();
-    droid.flow_oxygen()
+    solve_part2("day15_input.txt")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_is_fully_explored() {
+        let mut droid = Droid::from_data_file("day15_input.txt");
+        assert!(!droid.is_fully_explored());
+        droid.discover_map();
+        assert!(droid.is_fully_explored());
+    }
+
+    #[test]
+    fn test_dead_ends_and_junctions() {
+        let mut droid = Droid::from_data_file("day15_input.txt");
+        droid.discover_map();
+        let (oxygen, _) = droid.oxygen.unwrap();
+        assert!(droid.shortest_path(&point!(0, 0), &oxygen).is_some());
+        let dead_ends = droid.dead_ends();
+        let junctions = droid.junctions();
+        assert!(!dead_ends.is_empty());
+        assert!(dead_ends.iter().all(|p| droid.is_open(p) && droid.degree(p) == 1));
+        assert!(junctions.iter().all(|p| droid.is_open(p) && droid.degree(p) >= 3));
+        assert!(dead_ends.iter().all(|p| !junctions.contains(p)));
+        // In a tree-shaped maze, each junction adds (degree - 2) extra branch ends
+        let extra_branches: usize = junctions.iter().map(|p| droid.degree(p) - 2).sum();
+        assert_eq!(dead_ends.len(), extra_branches + 2);
+    }
+
+    #[test]
+    fn test_shortest_path() {
+        let mut droid = Droid::from_data_file("day15_input.txt");
+        droid.discover_map();
+        let (oxygen, _) = droid.oxygen.unwrap();
+        assert_eq!(droid.shortest_path(&point!(0, 0), &oxygen), Some(282));
+        assert_eq!(droid.shortest_path(&oxygen, &point!(0, 0)), Some(282));
+        assert_eq!(droid.shortest_path(&point!(0, 0), &point!(0, 0)), Some(0));
+        let wall = droid.map.iter().find(|(_, state)| state.tile == Tile::Wall).map(|(p, _)| *p).unwrap();
+        assert_eq!(droid.shortest_path(&point!(0, 0), &wall), None);
+        assert_eq!(droid.shortest_path(&wall, &point!(0, 0)), None);
+    }
+
+    #[test]
+    fn test_discover_map_wall_follower() {
+        let mut droid = Droid::from_data_file("day15_input.txt");
+        let map = droid.discover_map_wall_follower();
+        droid.discover_map();
+        let floor = |tiles: Vec<(Point2D, Tile)>| {
+            let mut points: Vec<Point2D> = tiles.into_iter()
+                .filter(|(_, tile)| matches!(tile, Tile::Floor | Tile::Oxygen))
+                .map(|(p, _)| p)
+                .collect();
+            points.sort_by_key(|p| (p.y, p.x));
+            points
+        };
+        assert_eq!(floor(map.iter().map(|(p, tile)| (*p, *tile)).collect()),
+                   floor(droid.map.iter().map(|(p, state)| (*p, state.tile)).collect()));
+        let oxygen = map.iter().find(|(_, tile)| **tile == Tile::Oxygen).map(|(p, _)| *p).unwrap();
+        let is_open = |p: &Point2D| matches!(map.get(p), Some(tile) if *tile != Tile::Wall);
+        assert_eq!(flood_fill(point!(0, 0), Connectivity::Four, is_open).get(&oxygen), Some(&282));
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(), 282);
@@ -1,12 +1,24 @@
 use std::cmp::{min, max};
-use std::iter::repeat;
 use std::ops::Range;
-use crate::util;
+use crate::util::{self, CycleDetector};
 
 fn read_input(filename: &str) -> Vec<i32> {
-    util::read_lines(filename)[0].chars().map(|x| x.to_string().parse().unwrap()).collect()
+    read_inputs(filename).into_iter().next().unwrap()
 }
 
+/// Read every non-empty line of the file as a separate signal
+fn read_inputs(filename: &str) -> Vec<Vec<i32>> {
+    util::read_lines(filename).unwrap()
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| util::from_digits(line.trim()).unwrap().into_iter().map(i32::from).collect())
+        .collect()
+}
+
+/// The repeating base pattern used by the FFT in the puzzle
+const BASE_PATTERN: [i32; 4] = [0, 1, 0, -1];
+
+/// A repeating run of pattern positions, where pattern position `p` applies to `data[p - 1]`
 #[derive(Copy,Clone,Debug)]
 struct Stride {
     offset: usize,
@@ -15,19 +27,27 @@ struct Stride {
 }
 
 impl Stride {
-    fn iter_chunks<'a, T>(&'a self, data: &'a[T]) -> impl Iterator<Item=&'a[T]> + 'a {
-        (self.offset .. data.len())
+    /// Iterate over the ranges of indexes covered by the stride in data of length `len`
+    fn iter_ranges(&self, len: usize) -> impl Iterator<Item=Range<usize>> {
+        let width = self.width;
+        // Pattern position 0 is skipped, so the first range might be 1 element short
+        (self.offset ..= len)
             .step_by(self.interval)
-            .map(move |i| &data[i .. min(i + self.width, data.len())])
+            .map(move |p| p.saturating_sub(1) .. min(p + width - 1, len))
+    }
+
+    #[cfg(test)]
+    fn iter_chunks<'a, T>(&'a self, data: &'a[T]) -> impl Iterator<Item=&'a[T]> + 'a {
+        self.iter_ranges(data.len()).map(move |r| &data[r])
     }
 }
 
-fn strides_for_index(i: usize) -> (Stride, Stride) {
+fn strides_for_index(i: usize, base: &[i32]) -> Vec<(i32, Stride)> {
     /*
     To generate the following digits:
     abcdefghijklmnop
 
-    Applies the following patterns to the input (a = 1, A = -1):
+    Applies the following patterns to the input (a = 1, A = -1) for base pattern [0, 1, 0, -1]:
     a_A_a_A_a_A_a_A_
     _bb__BB__bb__BB_
     __ccc___CCC___cc
@@ -37,69 +57,265 @@ fn strides_for_index(i: usize) -> (Stride, Stride) {
     So to generate e, i.e. index 4, is:
     - The sum of strides of 5, starting at 4, every 20
     - ... minus the sum of strides of 5, starting at 14, every 20
+
+    In general, each element of the base pattern is a stride of width i + 1, every
+    (i + 1) * base.len(), offset by one because the first pattern value is skipped. Elements of
+    the base pattern that are 0 don't contribute anything, so don't generate strides for them.
     */
     let width = i + 1;
-    let interval = width * 4;
-    return (
-        Stride {offset: i, interval, width},
-        Stride {offset: i + width * 2, interval, width},
-    );
+    let interval = width * base.len();
+    base
+        .iter()
+        .enumerate()
+        .filter(|(_, &m)| m != 0)
+        .map(|(k, &m)| (m, Stride {offset: k * width, interval, width}))
+        .collect()
 }
 
-fn next_value_at(data: &[i32], i: usize) -> i32 {
-    let (pos, neg) = strides_for_index(i);
-    let pos_val: i32 = pos.iter_chunks(data).map(|chunk| -> i32 {chunk.iter().sum()}).sum();
-    let neg_val: i32 = neg.iter_chunks(data).map(|chunk| -> i32 {chunk.iter().sum()}).sum();
-    return (pos_val - neg_val).abs() % 10;
+#[cfg(test)]
+fn next_value_at(data: &[i32], i: usize, base: &[i32]) -> i32 {
+    let value: i32 = strides_for_index(i, base)
+        .iter()
+        .map(|(m, stride)| m * stride.iter_chunks(data).map(|chunk| -> i32 {chunk.iter().sum()}).sum::<i32>())
+        .sum();
+    value.abs() % 10
 }
 
-fn step_range_in_place(data: &mut [i32], range: Range<usize>) {
-    let before = range.start .. min(range.end, data.len() / 2);
-    let after = max(range.start, data.len() / 2) .. range.end;
-    for i in before {
-        data[i] = next_value_at(data, i);
-    }
+/// Get the sum of every prefix of `data`, such that `data[a .. b]` sums to `prefix[b] - prefix[a]`
+fn prefix_sums(data: &[i32]) -> Vec<i32> {
+    let mut prefix = Vec::with_capacity(data.len() + 1);
+    prefix.push(0);
+    prefix.extend(data.iter().scan(0, |sum, &x| {
+        *sum += x;
+        Some(*sum)
+    }));
+    prefix
+}
+
+/// Equivalent to `next_value_at()`, but summing each chunk in O(1) from `prefix_sums()` of the data
+fn next_value_from_prefix(prefix: &[i32], i: usize, base: &[i32]) -> i32 {
+    let len = prefix.len() - 1;
+    let value: i32 = strides_for_index(i, base)
+        .iter()
+        .map(|(m, stride)| m * stride.iter_ranges(len).map(|r| prefix[r.end] - prefix[r.start]).sum::<i32>())
+        .sum();
+    value.abs() % 10
+}
+
+fn step_range_in_place(data: &mut [i32], range: Range<usize>, base: &[i32]) {
     /*
-    For every digit in the second half of the data, the pattern to create the new digit expands
-    to a multiplier of 1 for that position and everything after it, and therefore the digit is
-    created from a simple sum of the remaining data. A naive implementation would calculate the
-    sum once per digit, resulting in O(n^2) time complexity, however if we subtract each digit
-    from a running total calculated once, it becomes O(n).
+    For every digit in the second half of the data, a base pattern starting [0, 1, ...] expands to
+    a multiplier of 0 for everything before that position and 1 for that position and everything
+    after it, and therefore the digit is created from a simple sum of the remaining data. A naive
+    implementation would calculate the sum once per digit, resulting in O(n^2) time complexity,
+    however if we subtract each digit from a running total calculated once, it becomes O(n).
     */
-    let mut sum: i32 = data[after.clone()].iter().sum();
-    for i in after {
-        let old = data[i];
-        data[i] = sum % 10;
-        sum -= old;
+    let running_sum = base.len() >= 2 && base[0] == 0 && base[1] == 1;
+    let before = range.start .. if running_sum { min(range.end, data.len() / 2) } else { range.end };
+    let after = max(range.start, data.len() / 2) .. range.end;
+    if !before.is_empty() {
+        // Each digit is a sum of O(n / width) chunks, so summing chunks from a prefix sum of the
+        // input makes the phase O(n log n) instead of O(n^2)
+        let prefix = prefix_sums(data);
+        for i in before {
+            data[i] = next_value_from_prefix(&prefix, i, base);
+        }
+    }
+    if running_sum {
+        let mut sum: i32 = data[after.clone()].iter().sum();
+        for i in after {
+            let old = data[i];
+            data[i] = sum % 10;
+            sum -= old;
+        }
     }
 }
 
-pub fn part1() -> String {
-    let mut data = read_input("day16_input.txt");
+/// Apply phases of FFT to `data` until it repeats an earlier state, up to `max_phases` phases
+///
+/// Returns `Some((start, length))` if the state after `start` phases recurs every `length` phases,
+/// e.g. a fixed point has a length of 1, or `None` if no repeat was found.
+#[allow(dead_code)]
+fn run_until_cycle(data: &mut [i32], max_phases: usize) -> Option<(usize, usize)> {
     let n = data.len();
-    for _ in 0 .. 100 {
-        step_range_in_place(&mut data, 0 .. n);
+    let step = |data: &Vec<i32>| {
+        let mut next = data.clone();
+        step_range_in_place(&mut next, 0 .. n, &BASE_PATTERN);
+        next
+    };
+    let mut detector = CycleDetector::new(data.to_vec(), step);
+    let result = detector.find_cycle(max_phases);
+    data.copy_from_slice(detector.state());
+    result
+}
+
+/// Read up to `len` digits of `data` starting from `offset` as a string
+fn message(data: &[i32], offset: usize, len: usize) -> String {
+    util::digits_to_string(&data[offset .. min(offset + len, data.len())])
+}
+
+/// Apply `phases` phases of FFT to `signal`
+fn run_phases(signal: &[i32], phases: usize) -> Vec<i32> {
+    let mut data = signal.to_vec();
+    let n = data.len();
+    for _ in 0 .. phases {
+        step_range_in_place(&mut data, 0 .. n, &BASE_PATTERN);
     }
-    let output: Vec<String> = data[.. 8].iter().map(|x| format!("{}", x)).collect();
-    output.join("")
+    data
 }
 
-pub fn part2() -> String {
-    let position: usize = util::read_lines("day16_input.txt")[0][.. 7].parse().unwrap();
-    let mut data: Vec<i32> = repeat(read_input("day16_input.txt")).take(10000).flatten().collect();
+pub fn solve_part1(filename: &str) -> String {
+    message(&run_phases(&read_input(filename), 100), 0, 8)
+}
+
+pub fn part1() -> String {
+    solve_part1("day16_input.txt")
+}
+
+/// Decode the 8-digit message from `signal` repeated `repeat` times, after 100 phases of FFT
+///
+/// The message offset is read from the first 7 digits of the signal.
+fn decode_message(signal: &[i32], repeat: usize) -> String {
+    let position = signal[.. 7].iter().fold(0, |acc, &x| acc * 10 + x as usize);
+    let mut data: Vec<i32> = signal.iter().cloned().cycle().take(signal.len() * repeat).collect();
     let n = data.len();
+    // Digits are only affected by digits after them, so only need to run from the message onwards,
+    // but only the second half can be calculated without also calculating earlier digits
+    let range = if position >= n / 2 { position .. n } else { 0 .. n };
     for _ in 0 .. 100 {
-        // Only need to run the end of the data, because it's unaffected by anything earlier
-        step_range_in_place(data.as_mut_slice(), position .. n);
+        step_range_in_place(data.as_mut_slice(), range.clone(), &BASE_PATTERN);
     }
-    let output: Vec<String> = data[position .. position + 8].iter().map(|x| format!("{}", x)).collect();
-    output.join("")
+    message(&data, position, 8)
+}
+
+pub fn solve_part2(filename: &str) -> String {
+    decode_message(&read_input(filename), 10000)
+}
+
+pub fn part2() -> String {
+    solve_part2("day16_input.txt")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Apply one phase of FFT by directly calculating the pattern value for every element
+    fn naive_step(data: &[i32], base: &[i32]) -> Vec<i32> {
+        (0 .. data.len())
+            .map(|i| {
+                let value: i32 = data
+                    .iter()
+                    .enumerate()
+                    .map(|(j, x)| x * base[(j + 1) / (i + 1) % base.len()])
+                    .sum();
+                value.abs() % 10
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_step_range_in_place_example() {
+        let mut data: Vec<i32> = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let n = data.len();
+        step_range_in_place(&mut data, 0 .. n, &BASE_PATTERN);
+        assert_eq!(data, vec![4, 8, 2, 2, 6, 1, 5, 8]);
+        step_range_in_place(&mut data, 0 .. n, &BASE_PATTERN);
+        assert_eq!(data, vec![3, 4, 0, 4, 0, 4, 3, 8]);
+    }
+
+    #[test]
+    fn test_message_phases_example() {
+        // Phases of the small example as published in the puzzle
+        let mut data: Vec<i32> = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let n = data.len();
+        let mut phases: Vec<String> = Vec::new();
+        for _ in 0 .. 4 {
+            step_range_in_place(&mut data, 0 .. n, &BASE_PATTERN);
+            phases.push(message(&data, 0, 8));
+        }
+        assert_eq!(phases, vec!["48226158", "34040438", "03415518", "01029498"]);
+        assert_eq!(message(&data, 2, 3), "029");
+        assert_eq!(message(&data, 6, 8), "98");
+    }
+
+    #[test]
+    fn test_run_phases_examples() {
+        let signals = read_inputs("day16_example3.txt");
+        let results: Vec<String> = signals.iter().map(|s| message(&run_phases(s, 100), 0, 8)).collect();
+        assert_eq!(results, vec!["24176176", "73745418", "52432133"]);
+        assert_eq!(run_phases(&[1, 2, 3, 4, 5, 6, 7, 8], 0), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_next_value_from_prefix() {
+        // Pseudo-random digits from a linear congruential generator
+        let data: Vec<i32> = (0 .. 1000)
+            .scan(12345_u32, |state, _| {
+                *state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                Some((*state >> 16) as i32 % 10)
+            })
+            .collect();
+        let prefix = prefix_sums(&data);
+        for base in [BASE_PATTERN.to_vec(), vec![2, -1, 0, 1, 3], vec![1, -2]].iter() {
+            for i in 0 .. data.len() {
+                assert_eq!(next_value_from_prefix(&prefix, i, base), next_value_at(&data, i, base),
+                           "index {} base pattern {:?}", i, base);
+            }
+        }
+    }
+
+    #[test]
+    fn test_step_range_in_place_custom_base() {
+        let input: Vec<i32> = vec![8, 0, 8, 7, 1, 2, 2, 4, 5, 8, 5, 9, 1, 4, 5, 4, 6, 6, 1, 9];
+        for base in [vec![0, 1, 0, -1], vec![2, -1, 0, 1, 3], vec![1, -2], vec![0, 1, 1]].iter() {
+            let mut data = input.clone();
+            let mut expected = input.clone();
+            for _ in 0 .. 5 {
+                step_range_in_place(&mut data, 0 .. input.len(), base);
+                expected = naive_step(&expected, base);
+                assert_eq!(data, expected, "base pattern {:?}", base);
+            }
+        }
+    }
+
+    #[test]
+    fn test_run_until_cycle() {
+        // Already a fixed point
+        let mut data = vec![1, 0, 0, 0];
+        assert_eq!(run_until_cycle(&mut data, 10), Some((0, 1)));
+        assert_eq!(data, vec![1, 0, 0, 0]);
+        // 3,5,7 -> 4,2,7 -> 3,9,7 -> 4,6,7 -> 3,3,7 -> 4,0,7 -> 3,7,7 -> 4,4,7 -> 3,1,7 -> 4,8,7
+        let mut data = vec![3, 5, 7];
+        assert_eq!(run_until_cycle(&mut data, 10), Some((0, 10)));
+        let mut data = vec![3, 5, 7];
+        assert_eq!(run_until_cycle(&mut data, 9), None);
+    }
+
+    #[test]
+    fn test_decode_message_example() {
+        let signal: Vec<i32> = "03036732577212944063491565474664".bytes().map(|b| (b - b'0') as i32).collect();
+        assert_eq!(decode_message(&signal, 10000), "84462026");
+    }
+
+    #[test]
+    fn test_decode_message_first_half() {
+        // Offset of 9 is in the first half of the 2x repeated 20-digit signal
+        let signal: Vec<i32> = vec![0, 0, 0, 0, 0, 0, 9, 7, 1, 2, 2, 4, 5, 8, 5, 9, 1, 4, 5, 4];
+        let mut expected: Vec<i32> = signal.iter().cloned().cycle().take(signal.len() * 2).collect();
+        for _ in 0 .. 100 {
+            expected = naive_step(&expected, &BASE_PATTERN);
+        }
+        let expected: String = expected[9 .. 17].iter().map(|x| format!("{}", x)).collect();
+        assert_eq!(decode_message(&signal, 2), expected);
+    }
+
+    #[test]
+    fn test_solve_examples() {
+        assert_eq!(solve_part1("day16_example1.txt"), "24176176");
+        assert_eq!(solve_part2("day16_example2.txt"), "84462026");
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(), "82525123");
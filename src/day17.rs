@@ -1,10 +1,11 @@
 use std::collections::{BTreeMap, BTreeSet};
 use std::convert::TryFrom;
 use std::fmt::{Display, Formatter, Error};
-use std::iter::repeat_with;
 use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
+use crate::error::AocError;
 use crate::intcode::*;
-use crate::util::{Point2D, BoundingBox2D, Vector2D};
+use crate::util::{Grid2D, Point2D, Vector2D};
 
 // Maximum number of robot subroutines
 const MAX_ROUTINES: usize = 3;
@@ -73,6 +74,41 @@ impl Display for Direction {
     }
 }
 
+#[derive(Copy,Clone,Debug,Eq,PartialEq)]
+enum Tile {
+    Scaffold,
+    Space,
+    Robot(Direction),
+    /// The robot has fallen off the scaffold
+    Tumbling,
+}
+
+impl TryFrom<char> for Tile {
+    type Error = &'static str;
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value {
+            '#' => Ok(Tile::Scaffold),
+            '.' => Ok(Tile::Space),
+            'X' => Ok(Tile::Tumbling),
+            _ => Direction::try_from(value)
+                .map(Tile::Robot)
+                .map_err(|_| "not a valid camera tile (#, ., X, ^, v, <, or >)"),
+        }
+    }
+}
+
+impl Display for Tile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            Tile::Scaffold => write!(f, "#"),
+            Tile::Space => write!(f, "."),
+            Tile::Robot(d) => write!(f, "{}", d),
+            Tile::Tumbling => write!(f, "X"),
+        }
+    }
+}
+
 #[derive(Copy,Clone,Debug,Eq,PartialEq)]
 enum Command {
     Left,
@@ -88,6 +124,19 @@ impl Command {
         }
     }
 
+    fn is_turn(&self) -> bool {
+        matches!(self, Command::Left | Command::Right)
+    }
+
+    /// Get the turn in the opposite direction, i.e. the one that undoes this turn
+    fn opposite_turn(&self) -> Option<Command> {
+        match self {
+            Command::Left => Some(Command::Right),
+            Command::Right => Some(Command::Left),
+            Command::Forward(_) => None,
+        }
+    }
+
     fn to_string(&self) -> String {
         match self {
             Command::Left => "L".into(),
@@ -150,6 +199,56 @@ impl Path {
         return new;
     }
 
+    /// Get an equivalent path, i.e. ending at the same position and direction, without redundant
+    /// movement
+    ///
+    /// As well as merging consecutive forward movement like `simplify()`, opposite turns cancel
+    /// out, three turns the same way become one turn the other way, and going forward, turning
+    /// around and coming back is shortened to the net distance.
+    #[allow(dead_code)]
+    fn normalize(&self) -> Path {
+        let mut normalized = Path::new();
+        for c in self.iter() {
+            normalized.push_normalized(*c);
+        }
+        normalized
+    }
+
+    /// Add `command` to the end of an already normalized path, keeping it normalized
+    fn push_normalized(&mut self, command: Command) {
+        match (self.as_slice(), command) {
+            (_, Command::Forward(0)) => {},
+            ([.., Command::Forward(a)], Command::Forward(b)) => {
+                let n = a + b;
+                *self.last_mut().unwrap() = Command::Forward(n);
+            },
+            ([.., last], turn) if turn.is_turn() && last.opposite_turn() == Some(turn) => {
+                self.pop();
+            },
+            ([.., a, b], turn) if turn.is_turn() && *a == turn && *b == turn => {
+                let len = self.len();
+                self.truncate(len - 2);
+                self.push_normalized(turn.opposite_turn().unwrap());
+            },
+            ([.., Command::Forward(a), t1, t2], Command::Forward(b)) if t1.is_turn() && t1 == t2 => {
+                // Turned around and came back: only the difference in distance matters
+                let (a, turn) = (*a, *t1);
+                let len = self.len();
+                self.truncate(len - 3);
+                if a > b {
+                    self.push_normalized(Command::Forward(a - b));
+                    self.push_normalized(turn);
+                    self.push_normalized(turn);
+                } else {
+                    self.push_normalized(turn);
+                    self.push_normalized(turn);
+                    self.push_normalized(Command::Forward(b - a));
+                }
+            },
+            _ => self.push(command),
+        }
+    }
+
     fn find_duplicate_sequences(&self, min_commands: usize, max_length: usize) -> (DuplicateSequenceIndex, AvailableSequenceIndex) {
         assert!(min_commands > 0);
         // Mapping of (start, length) of a sequence to position of each repetition of that sequence
@@ -283,72 +382,76 @@ impl Robot {
     }
 }
 
+/// Reasons a single path can't be followed through the scaffold
+#[derive(Clone,Debug,Eq,PartialEq)]
+enum PathError {
+    /// The scaffold ends ahead but continues both left and right, so the way to go is unknown
+    AmbiguousJunction(Point2D),
+}
+
 #[derive(Debug)]
 struct Map {
-    data: Vec<Vec<char>>,
-    width: usize,
-    height: usize,
-    bbox: BoundingBox2D,
+    grid: Grid2D<Tile>,
     robot: Robot,
 }
 
 impl Map {
     fn new(data: &[String]) -> Map {
-        let data: Vec<Vec<char>> = data
+        Map::from_lines(data).unwrap()
+    }
+
+    /// Create a map from the rows of a video frame, finding (and removing) the robot
+    fn from_lines(data: &[String]) -> Result<Map, AocError> {
+        let chars = Grid2D::from_lines(data)?;
+        let tiles = chars.data
             .iter()
-            // Remove empty line(s)
-            .filter(|x| x.len() > 0)
-            // Turn each line into Vec<char>
-            .map(|x| x.chars().collect())
-            // Collect into Vec<Vec<char>>
-            .collect();
-        let height = data.len();
-        let width = data[0].len();
-        let mut bbox = BoundingBox2D::new(&point!(0, 0));
-        bbox.include(&point!((width - 1) as i32, (height - 1) as i32));
+            .map(|&c| Tile::try_from(c).map_err(|e| AocError::Parse(format!("{:?}: {}", c, e))))
+            .collect::<Result<Vec<Tile>, AocError>>()?;
         let mut map = Map {
-            data,
-            width,
-            height,
-            bbox,
+            grid: Grid2D {
+                data: tiles,
+                width: chars.width,
+                height: chars.height,
+                bbox: chars.bbox,
+            },
             robot: Robot { position: point!(-1, -1), direction: Direction::Up },
         };
-        for p in map.bbox.clone().iter() {
-            if let Some(Ok(d)) = map.get(&p).map(|c| Direction::try_from(*c)) {
+        for p in map.grid.bbox.clone().iter() {
+            if let Some(Tile::Robot(d)) = map.get(&p).cloned() {
                 map.robot = Robot { position: p, direction: d };
-                *map.get_mut(&p).unwrap() = '#';
+                *map.get_mut(&p).unwrap() = Tile::Scaffold;
                 break;
             }
         }
-        return map;
+        Ok(map)
     }
 
     #[allow(dead_code)]
     fn print(&self, robot: Option<&Robot>) {
-        for p in self.bbox.iter() {
-            match robot {
-                Some(Robot{position, direction}) if *position == p => {
-                    print!("{}", direction);
-                },
-                _ => {
-                    print!{"{}", self.get(&p).unwrap()}
-                },
-            }
-            if p.x == self.bbox.max.x {
-                println!();
+        for row in self.grid.bbox.iter_rows() {
+            for p in row {
+                match robot {
+                    Some(Robot{position, direction}) if *position == p => {
+                        print!("{}", direction);
+                    },
+                    _ => {
+                        print!{"{}", self.get(&p).unwrap()}
+                    },
+                }
             }
+            println!();
         }
     }
 
     fn find_intersections(&self) -> Vec<Point2D> {
         let mut intersections: Vec<Point2D> = Vec::new();
-        for p in self.bbox.iter() {
-            if let Some('#') = self.get(&p) {
+        for p in self.grid.bbox.iter() {
+            if let Some(Tile::Scaffold) = self.get(&p) {
                 let up = self.get(&(p +  point!(0, -1)));
                 let down = self.get(&(p +  point!(0, 1)));
                 let left = self.get(&(p +  point!(-1, 0)));
                 let right = self.get(&(p +  point!(1, 0)));
-                if let (Some('#'), Some('#'), Some('#'), Some('#')) = (up, down, left, right) {
+                if [up, down, left, right].iter().all(|t| *t == Some(&Tile::Scaffold)) {
                     intersections.push(p);
                 }
             }
@@ -356,86 +459,99 @@ impl Map {
         return intersections;
     }
 
-    fn find_path(&self) -> Path {
+    /// Get each intersection with its alignment parameter, the product of its coordinates
+    fn alignment_parameters(&self) -> Vec<(Point2D, i32)> {
+        self.find_intersections().into_iter().map(|p| (p, p.x * p.y)).collect()
+    }
+
+    /// Follow the scaffold from the robot's position, going straight over intersections, until it
+    /// ends
+    ///
+    /// Fails if the scaffold ends ahead but could be followed by turning either way.
+    fn find_path(&self) -> Result<Path, PathError> {
+        // Move one tile straight on, or turn first
+        const MOVES: [&[Command]; 3] = [
+            &[Command::Forward(1)],
+            &[Command::Left, Command::Forward(1)],
+            &[Command::Right, Command::Forward(1)],
+        ];
         let mut path = Path::new();
         let mut robot = self.robot.clone();
         loop {
-            let commands = vec![
-                vec![Command::Forward(1)],
-                vec![Command::Left, Command::Forward(1)],
-                vec![Command::Right, Command::Forward(1)],
-            ];
-            let robots: Vec<Robot> = commands.iter().map(|c| robot.apply_commands(c)).collect();
-            if let Some((c, r)) = commands.iter().zip(robots.iter()).filter(|(_, r)| self.is_on_scaffold(r)).nth(0) {
-                robot = r.clone();
-                path.extend_from_slice(c);
-            } else {
-                break;
+            let valid: Vec<(&[Command], Robot)> = MOVES.iter()
+                .map(|c| (*c, robot.apply_commands(c)))
+                .filter(|(_, r)| self.is_on_scaffold(r))
+                .collect();
+            match valid.as_slice() {
+                [] => break,
+                [(c, r), ..] if c.len() == 1 || valid.len() == 1 => {
+                    robot = r.clone();
+                    path.extend_from_slice(c);
+                },
+                _ => return Err(PathError::AmbiguousJunction(robot.position)),
             }
         }
-        return path;
+        Ok(path)
     }
 
-    fn get(&self, p: &Point2D) -> Option<&char> {
-        if self.bbox.contains(p) {
-            Some(&self.data[p.y as usize][p.x as usize])
-        } else {
-            None
-        }
+    fn get(&self, p: &Point2D) -> Option<&Tile> {
+        self.grid.get(p)
     }
 
-    fn get_mut(&mut self, p: &Point2D) -> Option<&mut char> {
-        if self.bbox.contains(p) {
-            Some(&mut self.data[p.y as usize][p.x as usize])
-        } else {
-            None
-        }
+    fn get_mut(&mut self, p: &Point2D) -> Option<&mut Tile> {
+        self.grid.get_mut(p)
     }
 
     fn is_on_scaffold(&self, robot: &Robot) -> bool {
         match self.get(&robot.position) {
-            Some('#') => true,
+            Some(Tile::Scaffold) => true,
             _ => false,
         }
     }
 }
 
-pub fn part1() -> i32 {
-    let mut emulator = Emulator::from_data_file("day17_input.txt");
+impl FromStr for Map {
+    type Err = AocError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<String> = s.lines().map(String::from).collect();
+        Map::from_lines(&lines)
+    }
+}
+
+pub fn solve_part1(filename: &str) -> i32 {
+    let mut emulator = Emulator::from_data_file(filename).unwrap();
     emulator.run();
-    let initial_map_data: Vec<String> = repeat_with(|| emulator.read_line())
-        .flatten()
-        .take_while(|s| s.len() > 0)
-        .collect();
+    let initial_map_data: Vec<String> = emulator.read_frame();
     let map = Map::new(&initial_map_data);
 //    map.print(Some(&map.robot));
-    let intersections = map.find_intersections();
-    intersections.iter().map(|p| p.x * p.y).sum()
+    map.alignment_parameters().iter().map(|(_, alignment)| alignment).sum()
 }
 
-pub fn part2() -> Word {
-    let mut emulator = Emulator::from_data_file("day17_input.txt");
+pub fn part1() -> i32 {
+    solve_part1("day17_input.txt")
+}
+
+pub fn solve_part2(filename: &str) -> Word {
+    let mut emulator = Emulator::from_data_file(filename).unwrap();
     // Wake the robot
     emulator.set(0, 2);
     // Run until the robot waits for input
     assert_eq!(emulator.run(), State::ReadWait);
 
     // Get the initial video frame & extract a scaffold map from it
-    let initial_map_data: Vec<String> = repeat_with(|| emulator.read_line())
-        .flatten()
-        .take_while(|s| s.len() > 0)
-        .collect();
+    let initial_map_data: Vec<String> = emulator.read_frame();
     let initial_map = Map::new(&initial_map_data);
 //    println!("Initial map:"); initial_map.print(Some(&initial_map.robot));
     // Find the path through the scaffold
-    let path = initial_map.find_path();
+    let path = initial_map.find_path().unwrap();
     let simplified_path = path.simplify();
     // Compress the path
     let (_duplicates, sequences_from) = simplified_path.find_duplicate_sequences(3, MAX_ROUTINE_LENGTH);
     let compressed_path = simplified_path.compress(&sequences_from, MAX_ROUTINES, MAX_ROUTINE_LENGTH).unwrap();
 
     // Feed the input to the robot
-    assert_eq!(emulator.read_line(), Some("Main:".to_string()));
+    emulator.run_to_prompt("Main:").unwrap();
     emulator.write_line(
         compressed_path.0
             .iter()
@@ -443,8 +559,7 @@ pub fn part2() -> Word {
             .collect::<Vec<String>>()
             .join(",")
             .as_str());
-    assert_eq!(emulator.run(), State::ReadWait);
-    assert_eq!(emulator.read_line(), Some("Function A:".to_string()));
+    emulator.run_to_prompt("Function A:").unwrap();
     emulator.write_line(
         compressed_path.1
             .get(&'A')
@@ -452,8 +567,7 @@ pub fn part2() -> Word {
             .unwrap_or("".to_string())
             .as_str()
     );
-    assert_eq!(emulator.run(), State::ReadWait);
-    assert_eq!(emulator.read_line(), Some("Function B:".to_string()));
+    emulator.run_to_prompt("Function B:").unwrap();
     emulator.write_line(
         compressed_path.1
             .get(&'B')
@@ -461,8 +575,7 @@ pub fn part2() -> Word {
             .unwrap_or("".to_string())
             .as_str()
     );
-    assert_eq!(emulator.run(), State::ReadWait);
-    assert_eq!(emulator.read_line(), Some("Function C:".to_string()));
+    emulator.run_to_prompt("Function C:").unwrap();
     emulator.write_line(
         compressed_path.1
             .get(&'C')
@@ -470,18 +583,14 @@ pub fn part2() -> Word {
             .unwrap_or("".to_string())
             .as_str()
     );
-    assert_eq!(emulator.run(), State::ReadWait);
-    assert_eq!(emulator.read_line(), Some("Continuous video feed?".to_string()));
+    emulator.run_to_prompt("Continuous video feed?").unwrap();
     emulator.write_line("n");
     // Run until the robot is finished
     assert_eq!(emulator.run(), State::Halt);
     assert_eq!(emulator.read_line(), Some("".to_string()));
 
     // Get the final video frame & extract a scaffold map from it
-    let _final_map_data: Vec<String> = repeat_with(|| emulator.read_line())
-        .flatten()
-        .take_while(|s| s.len() > 0)
-        .collect();
+    let _final_map_data: Vec<String> = emulator.read_frame();
 //    let final_map = Map::new(&_final_map_data);
 //    println!("Final map:");final_map.print(Some(&final_map.robot));
 
@@ -492,10 +601,143 @@ pub fn part2() -> Word {
     dust
 }
 
+pub fn part2() -> Word {
+    solve_part2("day17_input.txt")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_tile_from_char() {
+        assert_eq!(Tile::try_from('#'), Ok(Tile::Scaffold));
+        assert_eq!(Tile::try_from('.'), Ok(Tile::Space));
+        assert_eq!(Tile::try_from('^'), Ok(Tile::Robot(Direction::Up)));
+        assert_eq!(Tile::try_from('>'), Ok(Tile::Robot(Direction::Right)));
+        assert_eq!(Tile::try_from('v'), Ok(Tile::Robot(Direction::Down)));
+        assert_eq!(Tile::try_from('<'), Ok(Tile::Robot(Direction::Left)));
+        assert_eq!(Tile::try_from('X'), Ok(Tile::Tumbling));
+        assert!(Tile::try_from('?').is_err());
+        assert!(Tile::try_from('O').is_err());
+    }
+
+    #[test]
+    fn test_map_from_camera() {
+        let data: Vec<String> = ["..#..", "..#..", "#####", "..#..", "..^.."]
+            .iter().map(|row| row.to_string()).collect();
+        let map = Map::new(&data);
+        assert_eq!(map.robot, Robot { position: point!(2, 4), direction: Direction::Up });
+        assert_eq!(map.get(&point!(2, 4)), Some(&Tile::Scaffold));
+        assert_eq!(map.find_intersections(), vec![point!(2, 2)]);
+    }
+
+    #[test]
+    fn test_map_from_str_example1() {
+        let map: Map = "\
+..#..........
+..#..........
+#######...###
+#.#...#...#.#
+#############
+..#...#...#..
+..#####...^..
+".parse().unwrap();
+        let intersections = map.find_intersections();
+        assert_eq!(intersections.len(), 4);
+        assert_eq!(intersections.iter().map(|p| p.x * p.y).sum::<i32>(), 76);
+        assert!(matches!("..#\n.?#".parse::<Map>(), Err(AocError::Parse(_))));
+        assert!(matches!("..#\n.#".parse::<Map>(), Err(AocError::Parse(_))));
+    }
+
+    #[test]
+    fn test_alignment_parameters() {
+        let map: Map = "\
+..#..........
+..#..........
+#######...###
+#.#...#...#.#
+#############
+..#...#...#..
+..#####...^..
+".parse().unwrap();
+        let parameters = map.alignment_parameters();
+        assert_eq!(parameters, vec![
+            (point!(2, 2), 4),
+            (point!(2, 4), 8),
+            (point!(6, 4), 24),
+            (point!(10, 4), 40),
+        ]);
+        assert_eq!(parameters.iter().map(|(_, alignment)| alignment).sum::<i32>(), 76);
+    }
+
+    #[test]
+    fn test_map_from_str_example2() {
+        let map: Map = "\
+#######...#####
+#.....#...#...#
+#.....#...#...#
+......#...#...#
+......#...###.#
+......#.....#.#
+^########...#.#
+......#.#...#.#
+......#########
+........#...#..
+....#########..
+....#...#......
+....#...#......
+....#...#......
+....#####......
+".parse().unwrap();
+        assert_eq!(map.find_path().unwrap().simplify().to_string(), "R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2");
+    }
+
+    #[test]
+    fn test_find_path_ambiguous_junction() {
+        // Goes straight over the intersection, then the scaffold splits both ways at the top
+        let map: Map = "\
+..#####..
+....#....
+..#####..
+....#....
+....^....
+".parse().unwrap();
+        assert_eq!(map.find_path().map(|p| p.to_string()), Err(PathError::AmbiguousJunction(point!(4, 0))));
+        // With only one way to turn at the top, there's a path
+        let map: Map = "\
+....###..
+....#....
+..#####..
+....#....
+....^....
+".parse().unwrap();
+        assert_eq!(map.find_path().unwrap().simplify().to_string(), "4,R,2");
+    }
+
+    #[test]
+    fn test_path_normalize() {
+        use Command::*;
+        let normalize = |commands: &[Command]| Path::from(commands).normalize().to_string();
+        // Turn around part way back along the way we came
+        assert_eq!(normalize(&[Forward(4), Right, Right, Forward(2), Left, Forward(3)]), "2,R,3");
+        assert_eq!(normalize(&[Forward(2), Right, Right, Forward(5)]), "R,R,3");
+        // ... or all the way back, leaving only the turns
+        assert_eq!(normalize(&[Left, Forward(2), Right, Right, Forward(2), Right, Forward(1)]), "R,R,1");
+        assert_eq!(normalize(&[Left, Left, Left, Forward(4)]), "R,4");
+        assert_eq!(normalize(&[Right, Left, Forward(4), Forward(0), Forward(2)]), "6");
+        assert_eq!(normalize(&[Forward(1), Right, Right, Right, Right, Forward(1)]), "2");
+    }
+
+    #[test]
+    fn test_path_normalize_input() {
+        let mut emulator = Emulator::from_data_file("day17_input.txt").unwrap();
+        emulator.run();
+        let map = Map::new(&emulator.read_frame());
+        let path = map.find_path().unwrap().simplify();
+        assert_eq!(path.normalize().to_string(), path.to_string());
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(), 4112);
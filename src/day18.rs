@@ -17,11 +17,10 @@ the minimum total cost. However, the key/door behaviour adds a dependency tree a
 the dependency tree should constrain the TSP to a more reasonable set of possibilities than O(n!).
 
 */
-use std::collections::{HashSet, VecDeque, HashMap};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet, VecDeque, HashMap};
 use std::hash::Hash;
-use std::iter::FromIterator;
-use std::ops::{self, Deref, DerefMut};
-use crate::util::{self, BoundingBox2D, Point2D, Vector2D};
+use crate::util::{self, Grid2D, Point2D, Vector2D};
 
 const TILE_WALL: char = '#';
 const TILE_FLOOR: char = '.';
@@ -30,25 +29,78 @@ const DIRECTIONS: [Vector2D; 4] = [vector!(0, -1), vector!(1, 0), vector!(0, 1),
 
 
 /// Node: a point of interest in the map
-#[derive(Copy,Clone,Debug,Eq,PartialEq,Hash)]
+#[derive(Copy,Clone,Debug,Eq,PartialEq,Ord,PartialOrd,Hash)]
 enum Node {
     Entrance,
     Key(char),
 }
 
-/// Route: a sequence of nodes to visit, without specifics of adjacency, cost, etc.
-#[derive(Clone,Debug)]
-struct Route(Vec<Node>);
-deref!(Route, Vec<Node>);
+impl Node {
+    /// Get the bit representing this node in a `KeySet`
+    fn bit(&self) -> u32 {
+        match self {
+            Node::Key(k) => {
+                assert!(k.is_ascii_lowercase(), "invalid key {:?}", k);
+                1 << (*k as u32 - 'a' as u32)
+            },
+            Node::Entrance => 1 << 26,
+        }
+    }
+}
+
+
+/// KeySet: a set of nodes (i.e. keys held), with one bit per node so it's cheap to copy and compare
+#[derive(Copy,Clone,Debug,Default,Eq,PartialEq,Ord,PartialOrd,Hash)]
+struct KeySet(u32);
+
+impl KeySet {
+    fn new() -> KeySet {
+        KeySet(0)
+    }
+
+    fn insert(&mut self, node: Node) {
+        self.0 |= node.bit();
+    }
+
+    fn contains(&self, node: &Node) -> bool {
+        self.0 & node.bit() != 0
+    }
+
+    fn union(&self, other: &KeySet) -> KeySet {
+        KeySet(self.0 | other.0)
+    }
+
+    fn is_subset(&self, other: &KeySet) -> bool {
+        self.0 & !other.0 == 0
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
 
-impl Route {
-    fn new() -> Route {
-        Route(Vec::new())
+    fn iter(&self) -> impl Iterator<Item=Node> {
+        let bits = self.0;
+        (b'a' ..= b'z')
+            .map(|k| Node::Key(k as char))
+            .chain(std::iter::once(Node::Entrance))
+            .filter(move |n| bits & n.bit() != 0)
     }
+}
 
-    /// Iterate over `(from, to)` pairs along the route.
-    fn segments<'a>(&'a self) -> impl Iterator<Item=(Node, Node)> + 'a {
-        self.0.windows(2).map(|w| (w[0], w[1]))
+impl std::iter::FromIterator<Node> for KeySet {
+    fn from_iter<I: IntoIterator<Item=Node>>(iter: I) -> Self {
+        let mut set = KeySet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<Node> for KeySet {
+    fn extend<I: IntoIterator<Item=Node>>(&mut self, iter: I) {
+        for node in iter {
+            self.insert(node);
+        }
     }
 }
 
@@ -59,15 +111,14 @@ struct Edge {
     /// Cost: the number of steps to get between the two nodes
     cost: usize,
     /// Requirements: the keys that must be held (i.e. nodes that must have been visited) to use the edge
-    requirements: HashSet<Node>,
+    requirements: KeySet,
 }
 
 impl Edge {
     fn new() -> Edge {
         Edge {
             cost: 0,
-            // TODO: optimisation: use a u32, use 1 bit per key, check requirements with bitmasks
-            requirements: HashSet::new(),
+            requirements: KeySet::new(),
         }
     }
 }
@@ -76,28 +127,20 @@ impl Edge {
 /// Map: the 2D tile representation of the input map
 #[derive(Debug)]
 struct Map {
-    data: Vec<char>,
-    width: usize,
-    height: usize,
-    bbox: BoundingBox2D,
+    grid: Grid2D<char>,
     entrance: Point2D,
 }
 
 impl Map {
     /// Construct the map from an input file
     fn from_data_file(filename: &str) -> Map {
-        let lines = util::read_lines(filename);
-        let height = lines.len();
-        let width = lines[0].len();
-        let mut bbox = BoundingBox2D::new(&point!(0, 0));
-        bbox.include(&point!(width as i32 - 1, height as i32 - 1));
-        let mut data = Vec::new();
-        data.reserve(width * height);
-        for line in lines {
-            data.extend(line.chars())
-        }
-        let mut map = Map {data, width, height, bbox, entrance: point!(0, 0)};
-        map.entrance = map.bbox.iter()
+        Map::from_lines(&util::read_lines(filename).unwrap())
+    }
+
+    /// Construct the map from the rows of tiles
+    fn from_lines(lines: &[String]) -> Map {
+        let mut map = Map {grid: Grid2D::from_lines(lines).unwrap(), entrance: point!(0, 0)};
+        map.entrance = map.grid.bbox.iter()
             .find(|p| map.get(p).unwrap() == TILE_ENTRANCE)
             .unwrap();
         return map;
@@ -105,11 +148,72 @@ impl Map {
 
     /// Get the tile character at `p`
     fn get(&self, p: &Point2D) -> Option<char> {
-        if !self.bbox.contains(p) {
-            None
-        } else {
-            Some(self.data[p.y as usize * self.width + p.x as usize])
+        self.grid.get(p).cloned()
+    }
+
+    /// Classify a tile as a node: the entrance and keys are nodes, everything else isn't
+    fn tile_node(tile: char) -> Option<Node> {
+        match tile {
+            TILE_ENTRANCE => Some(Node::Entrance),
+            key if key.is_ascii_lowercase() => Some(Node::Key(key)),
+            _ => None,
+        }
+    }
+
+    /// Find the nodes adjacent to every node, see `find_adjacent()`
+    ///
+    /// Unlike `NodeGraph` this keeps every route between nodes, including those that branch away
+    /// from a node, so the edge costs are the real distances.
+    fn find_all_adjacent(&self) -> HashMap<Node, HashMap<Node, Edge>> {
+        self.grid.bbox.iter()
+            .filter_map(|p| self.get(&p).and_then(Map::tile_node))
+            .map(|n| (n, self.find_adjacent(n)))
+            .collect()
+    }
+
+    /// Find the nodes adjacent to `n`, see `find_adjacent_with()`
+    fn find_adjacent(&self, n: Node) -> HashMap<Node, Edge> {
+        self.find_adjacent_with(n, Map::tile_node)
+    }
+
+    /// Find the nodes adjacent to `n`, i.e. reachable without passing through another node, and
+    /// the edge to each of them, where `is_node` decides which tiles are nodes
+    ///
+    /// Doors that aren't nodes are passable, adding their key to the edge's requirements.
+    fn find_adjacent_with(&self, n: Node, is_node: impl Fn(char) -> Option<Node>) -> HashMap<Node, Edge> {
+        let mut adjacent: HashMap<Node, Edge> = HashMap::new();
+        let start = match self.grid.bbox.iter().find(|p| self.get(p).and_then(&is_node) == Some(n)) {
+            Some(p) => p,
+            None => return adjacent,
+        };
+        let mut queue: VecDeque<(Point2D, Edge)> = VecDeque::new();
+        queue.push_back((start, Edge::new()));
+        let mut seen: HashSet<Point2D> = HashSet::new();
+        seen.insert(start);
+
+        while let Some((pos, edge)) = queue.pop_front() {
+            for d in DIRECTIONS.iter().cloned() {
+                let next = pos + d;
+                if !seen.insert(next) {
+                    continue;
+                }
+                let tile = match self.get(&next) {
+                    Some(TILE_WALL) | None => continue,
+                    Some(tile) => tile,
+                };
+                let mut next_edge = Edge{cost: edge.cost + 1, requirements: edge.requirements};
+                if let Some(node) = is_node(tile) {
+                    // Stop at the node, keeping the shortest edge if it's reachable more than one way
+                    adjacent.entry(node).or_insert(next_edge);
+                    continue;
+                }
+                if tile.is_ascii_uppercase() {
+                    next_edge.requirements.insert(Node::Key(tile.to_ascii_lowercase()));
+                }
+                queue.push_back((next, next_edge));
+            }
         }
+        adjacent
     }
 }
 
@@ -122,17 +226,29 @@ struct NodeGraph {
     adjacent: HashMap<Node, HashMap<Node, Edge>>,
     /// Requirements (nodes visited AKA keys held) that must be met to visit a node for the first
     /// time, i.e. the sum of all edge requirements to get to each node from Entrance
-    requirements: HashMap<Node, HashSet<Node>>,
+    requirements: HashMap<Node, KeySet>,
+    /// Whether there's only one way to get between any two places in the map, see `is_tree()`
+    tree: bool,
 }
 
 impl NodeGraph {
     fn new() -> NodeGraph {
         NodeGraph {
             adjacent: HashMap::new(),
-            requirements: HashMap::from(vec![(Node::Entrance, HashSet::new())].into_iter().collect()),
+            requirements: vec![(Node::Entrance, KeySet::new())].into_iter().collect(),
+            tree: true,
         }
     }
 
+    /// Check if the map this graph came from has no cycles, i.e. exactly one route between any
+    /// two nodes
+    ///
+    /// The edges and requirements are only accurate if this is true, otherwise they only reflect
+    /// whichever route the flood fill happened to find first.
+    fn is_tree(&self) -> bool {
+        self.tree
+    }
+
     /// Add an edge from `a` to `b` specified by `e`
     ///
     /// Also adds the reverse edge, but the `a -> b` direction is used to determine the dependency
@@ -145,28 +261,34 @@ impl NodeGraph {
 
         // Record the dependencies for getting to b:
         // 1) Must have been to every node in the edge's requirements (i.e. picked up the relevant keys)
-        let mut b_deps: HashSet<Node> = e.requirements.clone();
+        let mut b_deps: KeySet = e.requirements;
         // 2) Must have satisfied the requirements to get to a first
         if let Some(a_deps) = self.requirements.get(&a) {
-            b_deps.extend(a_deps);
+            b_deps = b_deps.union(a_deps);
         }
         // (Update the dependency set)
-        self.requirements.entry(b).or_insert(HashSet::new()).extend(b_deps);
+        let deps = self.requirements.entry(b).or_default();
+        *deps = deps.union(&b_deps);
     }
 
-    /// Get set of nodes adjacent to `n`, excluding `from`
-    fn get_adjacent_nodes(&self, n: Node, from: Node) -> HashSet<Node> {
-        let mut adjacent: HashSet<Node> = self.adjacent.get(&n)
-            .map(|x| x.keys().cloned().collect())
-            .unwrap_or(HashSet::new());
-        adjacent.remove(&from);
-        return adjacent;
+    /// Get the moves from a search state to every unvisited node whose requirements are met, where
+    /// `distances` is the number of steps between each pair of nodes
+    ///
+    /// Panics if the graph isn't a tree, because otherwise the requirements don't account for every
+    /// route to a node.
+    fn successors<'a>(&'a self, distances: &'a HashMap<(Node, Node), usize>) -> impl Fn(SearchState) -> Vec<(Node, usize)> + 'a {
+        assert!(self.is_tree(), "node graph successors require an acyclic map");
+        move |(last, keys)| {
+            self.requirements.iter()
+                .filter(|(next, reqs)| !keys.contains(next) && reqs.is_subset(&keys))
+                .map(|(next, _)| (*next, distances[&(last, *next)]))
+                .collect()
+        }
     }
 }
 
 impl From<&Map> for NodeGraph {
     fn from(map: &Map) -> Self {
-        // TODO: check than the acyclic graph assumption holds true - should only see each node once
         let mut paths = NodeGraph::new();
         let mut queue: VecDeque<(Point2D, Edge, Point2D, Node)> = VecDeque::new();
         queue.push_back((map.entrance.clone(), Edge::new(), map.entrance.clone(), Node::Entrance));
@@ -178,6 +300,11 @@ impl From<&Map> for NodeGraph {
                 let next = pos + d;
                 // Don't backtrack
                 if seen.contains(&next) {
+                    // Somewhere open that was already reached, but not where we just came from, is
+                    // a second way to get there
+                    if next != from_pos && matches!(map.get(&next), Some(tile) if tile != TILE_WALL) {
+                        paths.tree = false;
+                    }
                     continue;
                 }
                 seen.insert(next);
@@ -188,11 +315,11 @@ impl From<&Map> for NodeGraph {
                     Some(TILE_WALL) | None => {},
                     // Floor: just advance one step
                     Some(TILE_FLOOR) => {
-                        queue.push_back((next, Edge{cost: edge.cost + 1, requirements: edge.requirements.clone()}, pos.clone(), from_node));
+                        queue.push_back((next, Edge{cost: edge.cost + 1, requirements: edge.requirements}, pos.clone(), from_node));
                     },
                     // Door: add to the set of requirements, advance one step
                     Some(door) if 'A' <= door && door <= 'Z' => {
-                        let mut requirements = edge.requirements.clone();
+                        let mut requirements = edge.requirements;
                         // Convert door to the required key
                         requirements.insert(Node::Key(door.to_ascii_lowercase()));
                         queue.push_back((next, Edge{cost: edge.cost + 1, requirements}, pos.clone(), from_node));
@@ -200,7 +327,7 @@ impl From<&Map> for NodeGraph {
                     // Key: end path and record it, start new path
                     Some(key) if 'a' <= key && key <= 'z' => {
                         let node = Node::Key(key);
-                        paths.add_edge(from_node, node, Edge{cost: edge.cost + 1, requirements: edge.requirements.clone()});
+                        paths.add_edge(from_node, node, Edge{cost: edge.cost + 1, requirements: edge.requirements});
                         queue.push_back((next, Edge::new(), pos.clone(), node));
                     },
                     unknown => panic!(format!("unknown tile: {:?}", unknown)),
@@ -211,222 +338,189 @@ impl From<&Map> for NodeGraph {
     }
 }
 
-/// Depth-first-search iteration of valid node visit orderings
-#[derive(Debug)]
-struct RouteGenerator<'a> {
-    path_cache: &'a mut PathCache<'a>,
-    stack: Vec<Route>,
-}
+/// Search state: the current node and the nodes visited so far (including the current node)
+type SearchState = (Node, KeySet);
 
-impl<'a> RouteGenerator<'a> {
-    fn new(path_cache: &'a mut PathCache<'a>) -> RouteGenerator<'a> {
-        RouteGenerator {
-            path_cache,
-            stack: vec![Route(vec![Node::Entrance])],
-        }
-    }
+/// A lower bound on the cost of visiting every remaining node, for A* search
+#[derive(Debug)]
+struct Heuristic {
+    /// Shortest distance between every pair of nodes, ignoring doors
+    distances: HashMap<(Node, Node), usize>,
+    nodes: KeySet,
 }
 
-impl<'a> Iterator for RouteGenerator<'a> {
-    type Item = Route;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        // Loop until we return something
-        loop {
-            if let Some(route) = self.stack.pop() {
-                let next_routes = self.path_cache.continue_route(&route);
-                if next_routes.is_empty() {
-                    break Some(route);
-                } else {
-                    self.stack.extend(next_routes);
+impl Heuristic {
+    fn new(adjacent: &HashMap<Node, HashMap<Node, Edge>>) -> Heuristic {
+        let mut distances: HashMap<(Node, Node), usize> = HashMap::new();
+        for &start in adjacent.keys() {
+            let mut queue: BinaryHeap<Reverse<(usize, Node)>> = BinaryHeap::new();
+            queue.push(Reverse((0, start)));
+            while let Some(Reverse((cost, node))) = queue.pop() {
+                if distances.contains_key(&(start, node)) {
+                    continue;
+                }
+                distances.insert((start, node), cost);
+                for (next, edge) in adjacent[&node].iter() {
+                    queue.push(Reverse((cost + edge.cost, *next)));
                 }
-            } else {
-                break None;
             }
         }
-    }
-}
-
-
-/// A caching calculator of paths, reachable sets, etc.
-#[derive(Debug)]
-struct PathCache<'a> {
-    /// The node graph this path cache relates to (assumes that the node graph is fully populated).
-    nodegraph: &'a NodeGraph,
-    /// See get_reachable().
-    reachable: HashMap<(Node, Node), HashSet<Node>>,
-    /// See get_path().
-    paths: HashMap<(Node, Node), Path>,
-}
-
-impl<'a> PathCache<'a> {
-    fn new(nodegraph: &'a NodeGraph) -> PathCache<'a> {
-        PathCache {
-            nodegraph,
-            reachable: HashMap::new(),
-            paths: HashMap::new(),
+        Heuristic {
+            distances,
+            nodes: adjacent.keys().cloned().collect(),
         }
     }
 
-    /// Get the set of nodes reachable in the direction of `from -> to`.
+    /// Estimate the remaining cost from `state` as the distance to the furthest unvisited node,
+    /// ignoring doors
     ///
-    /// `(from, to)` must be a path segment, i.e. adjacent, not an abstract route segment.
-    fn get_reachable(&mut self, from: Node, to: Node) -> HashSet<Node> {
-        assert!(
-            self.nodegraph.adjacent.get(&from).and_then(|x| x.get(&to)).is_some(),
-            "get_reachable: from and to must be adjacent",
-        );
-        if let Some(set) = self.reachable.get(&(from, to)).cloned() {
-            set
-        } else {
-            let mut set: HashSet<Node> = HashSet::new();
-            // Obviously can reach `to` by following `from -> to`
-            set.insert(to);
-            // Recursively include anything reachable from `to`
-            for node in self.nodegraph.get_adjacent_nodes(to, from) {
-                let more = self.get_reachable(to, node);
-                set.extend(more);
-            }
-            self.reachable.insert((from, to), set.clone());
-            set
-        }
+    /// That node must be visited eventually, so the estimate never exceeds the real cost.
+    fn estimate(&self, (last, keys): SearchState) -> usize {
+        self.nodes.iter()
+            .filter(|n| !keys.contains(n))
+            .map(|n| self.distances[&(last, n)])
+            .max()
+            .unwrap_or(0)
     }
+}
 
-    /// Get valid extensions of `route`, taking into account dependencies and nodes already visited
-    fn continue_route(&mut self, route: &Route) -> Vec<Route> {
-        let last = route.last().unwrap();
-        let mut routes: Vec<Route> = Vec::new();
-        let keys = HashSet::from_iter(route.iter().cloned());
-        for (next, reqs) in self.nodegraph.requirements.iter() {
-            // Already visited this one? Don't want to revisit nodes for no reason, so skip it.
-            if keys.contains(next) { continue; }
-            // Still got unmet requirements? Not a valid route, so skip it.
-            if reqs.difference(&keys).count() > 0 { continue; }
-            // Visits other nodes we haven't visited yet? Cut down some of the "factorial time" by
-            // avoiding longer paths that visit the same set of nodes.
-            let path = self.get_path(*last, *next);
-            if path.route[.. path.route.len() - 1].iter().any(|n| !keys.contains(n)) { continue; }
-            // Create a new route if we survived this far!
-            let mut next_route = Route::new();
-            next_route.extend_from_slice(route);
-            next_route.push(*next);
-            routes.push(next_route);
+/// Find the lowest cost of visiting every node in `nodes`, starting from `start`, using A* search
+/// with `successors` giving the possible moves from each state and `heuristic` estimating the
+/// remaining cost of each state
+fn search(
+    nodes: KeySet,
+    start: SearchState,
+    mut successors: impl FnMut(SearchState) -> Vec<(Node, usize)>,
+    heuristic: impl Fn(SearchState) -> usize,
+) -> Option<usize> {
+    let mut costs: HashMap<SearchState, usize> = HashMap::new();
+    let mut queue: BinaryHeap<Reverse<(usize, usize, SearchState)>> = BinaryHeap::new();
+    costs.insert(start, 0);
+    queue.push(Reverse((heuristic(start), 0, start)));
+    while let Some(Reverse((_, cost, (last, keys)))) = queue.pop() {
+        if keys == nodes {
+            return Some(cost);
         }
-        return routes;
-    }
-
-    /// Get the path to travel the `from -> to` route segment.
-    ///
-    /// Every node is connected to the graph, and the graph has no cycles, so there is exactly one
-    /// non-backtracking route between any 2 nodes.
-    fn get_path(&mut self, from: Node, to: Node) -> Path {
-        // TODO: caching
-        let mut path = Path {
-            route: Route(vec![from]),
-            cost: 0,
-        };
-        let mut prev = from;
-        let mut curr = from;
-        // Loop until we find the destination
-        'outer: while curr != to {
-            // Look at possible next nodes
-            for next in self.nodegraph.get_adjacent_nodes(curr, prev) {
-                // See if destination is reachable via this node
-                if self.get_reachable(curr, next).contains(&to) {
-                    let edge = self.nodegraph.adjacent.get(&curr).unwrap().get(&next).unwrap();
-                    path.route.push(next);
-                    path.cost += edge.cost;
-                    prev = curr;
-                    curr = next;
-                    continue 'outer;
-                }
-            }
-            panic!(format!("no route between {:?} and {:?}", from, to));
+        // Skip states that were already reached more cheaply
+        if matches!(costs.get(&(last, keys)), Some(&best) if best < cost) {
+            continue;
         }
-        return path;
-    }
-
-    /// Get the concrete path to travel the abstract `route`.
-    fn get_path_from_route(&mut self, route: &Route) -> Path {
-        let mut path = Path {
-            route: Route(vec![route[0]]),
-            cost: 0,
-        };
-        for (from, to) in route.segments() {
-            path += &self.get_path(from, to);
+        for (next, step_cost) in successors((last, keys)) {
+            let mut next_keys = keys;
+            next_keys.insert(next);
+            let next_state = (next, next_keys);
+            let next_cost = cost + step_cost;
+            let improved = match costs.get(&next_state) {
+                Some(&best) => next_cost < best,
+                None => true,
+            };
+            if improved {
+                costs.insert(next_state, next_cost);
+                queue.push(Reverse((next_cost + heuristic(next_state), next_cost, next_state)));
+            }
         }
-        return path;
     }
+    None
 }
 
-
-/// Path: a route where consecutive nodes are always adjacent, with cost calculated too
-#[derive(Clone,Debug)]
-struct Path {
-    route: Route,
-    cost: usize,
-}
-
-impl Path {
-    /// Iterate over `(from, to)` pairs along the path.
-    fn segments<'a>(&'a self) -> impl Iterator<Item=(Node, Node)> + 'a {
-        self.route.windows(2).map(|w| (w[0], w[1]))
+/// Get the moves from a search state along the edges of `adjacent` whose requirements are met
+fn adjacent_successors(adjacent: &HashMap<Node, HashMap<Node, Edge>>) -> impl Fn(SearchState) -> Vec<(Node, usize)> + '_ {
+    move |(last, keys)| {
+        adjacent[&last].iter()
+            .filter(|(_, edge)| edge.requirements.is_subset(&keys))
+            .map(|(next, edge)| (*next, edge.cost))
+            .collect()
     }
 }
 
-impl ops::Add<&Path> for Path {
-    type Output = Path;
-
-    fn add(self, rhs: &Path) -> Self::Output {
-        assert!(self.route.len() == 0 || rhs.route.len() == 0 || self.route.last() == rhs.route.first());
-        let mut path = self.clone();
-        path += rhs;
-        return path;
+/// Find the fewest steps to collect every key in `map`
+fn collect_keys(map: &Map) -> usize {
+    let adjacent = map.find_all_adjacent();
+    let heuristic = Heuristic::new(&adjacent);
+    let estimate = |state| heuristic.estimate(state);
+    let start = (Node::Entrance, [Node::Entrance].iter().cloned().collect());
+    let node_graph = NodeGraph::from(map);
+    if node_graph.is_tree() {
+        // Every node can be reached directly once its requirements are met, which skips expanding
+        // states for keys that are only passed through on the way
+        search(heuristic.nodes, start, node_graph.successors(&heuristic.distances), estimate).unwrap()
+    } else {
+        search(heuristic.nodes, start, adjacent_successors(&adjacent), estimate).unwrap()
     }
 }
 
-impl ops::AddAssign<&Path> for Path {
-    fn add_assign(&mut self, rhs: &Path) {
-        assert!(self.route.len() == 0 || rhs.route.len() == 0 || self.route.last() == rhs.route.first());
-        self.route.extend(rhs.route.iter().skip(1));
-        self.cost += rhs.cost;
-    }
+fn shortest_path(filename: &str) -> usize {
+    collect_keys(&Map::from_data_file(filename))
 }
 
-
-fn shortest_path(filename: &str) -> usize {
-    let map = Map::from_data_file(filename);
-    let node_graph = NodeGraph::from(&map);
-    {
-        let mut path_cache = PathCache::new(&node_graph);
-        let route_gen = RouteGenerator::new(&mut path_cache);
-        println!("number of routes: {}", route_gen.count());
-    }
-    {
-        let mut path_cache = PathCache::new(&node_graph);
-        let route_gen = RouteGenerator::new(&mut path_cache);
-        for r in route_gen {
-            println!("route: {:?}", &r);
+/// Split the map into the four vaults of part 2, each with its own entrance
+///
+/// Each vault is the part of the map reachable from its entrance, with everything else walled off.
+/// Vaults are solved on their own, so doors whose keys are in another vault are opened, assuming
+/// the robot in that vault will have collected the key by the time it's needed.
+fn split_vaults(lines: &[String]) -> Vec<Map> {
+    let mut grid = Grid2D::from_lines(lines).unwrap();
+    let centre = grid.bbox.iter().find(|p| grid[*p] == TILE_ENTRANCE).unwrap();
+    let mut entrances: Vec<Point2D> = Vec::new();
+    for y in -1 ..= 1 {
+        for x in -1 ..= 1 {
+            let p = centre + vector!(x, y);
+            if x == 0 || y == 0 {
+                grid[p] = TILE_WALL;
+            } else {
+                grid[p] = TILE_ENTRANCE;
+                entrances.push(p);
+            }
         }
     }
-//    route_gen.map(|r| {
-//        println!("route: {:?}", &r);
-////        path_cache.get_path_from_route(&r).cost
-//    }).min().unwrap()
-    0
+    entrances.into_iter().map(|entrance| {
+        let mut vault: HashSet<Point2D> = HashSet::new();
+        let mut queue: VecDeque<Point2D> = VecDeque::new();
+        vault.insert(entrance);
+        queue.push_back(entrance);
+        while let Some(pos) = queue.pop_front() {
+            for d in DIRECTIONS.iter().cloned() {
+                let next = pos + d;
+                if matches!(grid.get(&next), Some(&tile) if tile != TILE_WALL) && vault.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        let keys: HashSet<char> = vault.iter().map(|p| grid[*p]).filter(char::is_ascii_lowercase).collect();
+        let rows: Vec<String> = (grid.bbox.min.y ..= grid.bbox.max.y).map(|y| {
+            (grid.bbox.min.x ..= grid.bbox.max.x).map(|x| {
+                let p = point!(x, y);
+                match grid[p] {
+                    _ if !vault.contains(&p) => TILE_WALL,
+                    door if door.is_ascii_uppercase() && !keys.contains(&door.to_ascii_lowercase()) => TILE_FLOOR,
+                    tile => tile,
+                }
+            }).collect()
+        }).collect();
+        Map::from_lines(&rows)
+    }).collect()
+}
+
+pub fn solve_part1(filename: &str) -> usize {
+    shortest_path(filename)
 }
 
 pub fn part1() -> usize {
-    shortest_path("day18_example4.txt")
+    solve_part1("day18_input.txt")
+}
+
+pub fn solve_part2(filename: &str) -> usize {
+    split_vaults(&util::read_lines(filename).unwrap()).iter().map(collect_keys).sum()
 }
 
-pub fn part2() -> i32 {
-    0
+pub fn part2() -> usize {
+    solve_part2("day18_input.txt")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::BTreeSet;
 
     #[test]
     fn test_shortest_path_example1() {
@@ -453,13 +547,174 @@ mod tests {
         assert_eq!(shortest_path("day18_example5.txt"), 81);
     }
 
+    #[test]
+    fn test_node_graph_is_tree() {
+        for i in 1 ..= 5 {
+            let map = Map::from_data_file(&format!("day18_example{}.txt", i));
+            assert!(NodeGraph::from(&map).is_tree(), "example {}", i);
+        }
+        let lines: Vec<String> = [
+            "#######",
+            "#a...b#",
+            "#.###.#",
+            "#..@..#",
+            "#######",
+        ].iter().map(|row| row.to_string()).collect();
+        let map = Map {grid: Grid2D::from_lines(&lines).unwrap(), entrance: point!(3, 3)};
+        assert!(!NodeGraph::from(&map).is_tree());
+    }
+
+    #[test]
+    #[should_panic(expected = "node graph successors require an acyclic map")]
+    fn test_node_graph_successors_require_tree() {
+        let lines: Vec<String> = [
+            "#######",
+            "#a...b#",
+            "#.###.#",
+            "#..@..#",
+            "#######",
+        ].iter().map(|row| row.to_string()).collect();
+        let map = Map::from_lines(&lines);
+        let heuristic = Heuristic::new(&map.find_all_adjacent());
+        let node_graph = NodeGraph::from(&map);
+        let successors = node_graph.successors(&heuristic.distances);
+        successors((Node::Entrance, KeySet::new()));
+    }
+
+    #[test]
+    fn test_keyset_matches_btreeset() {
+        let nodes: Vec<Node> = (b'a' ..= b'z').map(|k| Node::Key(k as char)).chain(vec![Node::Entrance]).collect();
+        for i in 1 ..= 5 {
+            let map = Map::from_data_file(&format!("day18_example{}.txt", i));
+            let node_graph = NodeGraph::from(&map);
+            let mut all_keys = KeySet::new();
+            let mut all_btree: BTreeSet<Node> = BTreeSet::new();
+            for (node, reqs) in node_graph.requirements.iter() {
+                let btree: BTreeSet<Node> = reqs.iter().collect();
+                assert_eq!(reqs.len(), btree.len());
+                assert_eq!(btree.iter().cloned().collect::<KeySet>(), *reqs);
+                for n in nodes.iter() {
+                    assert_eq!(reqs.contains(n), btree.contains(n));
+                }
+                // Requirements to reach a node never include the node itself
+                assert!(!reqs.contains(node));
+                all_keys = all_keys.union(reqs);
+                all_btree.extend(btree.iter().cloned());
+                all_keys.insert(*node);
+                all_btree.insert(*node);
+            }
+            assert_eq!(all_keys.iter().collect::<BTreeSet<Node>>(), all_btree);
+            assert_eq!(all_keys.len(), node_graph.requirements.len());
+            for reqs in node_graph.requirements.values() {
+                let btree: BTreeSet<Node> = reqs.iter().collect();
+                assert_eq!(reqs.is_subset(&all_keys), btree.is_subset(&all_btree));
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_adjacent() {
+        let map = Map::from_data_file("day18_example1.txt");
+        let adjacent = map.find_adjacent(Node::Entrance);
+        let mut costs: Vec<(Node, usize, KeySet)> = adjacent.iter().map(|(n, e)| (*n, e.cost, e.requirements)).collect();
+        costs.sort_by_key(|(n, _, _)| *n);
+        assert_eq!(costs, vec![
+            (Node::Key('a'), 2, KeySet::new()),
+            (Node::Key('b'), 4, [Node::Key('a')].iter().cloned().collect()),
+        ]);
+        // Every key is adjacent to the entrance, so nothing is adjacent to `a` except the entrance
+        let adjacent = map.find_adjacent(Node::Key('a'));
+        assert_eq!(adjacent.keys().collect::<Vec<&Node>>(), vec![&Node::Entrance]);
+        assert_eq!(adjacent[&Node::Entrance].cost, 2);
+        // Treating only the entrance as a node finds nothing adjacent to it
+        let adjacent = map.find_adjacent_with(Node::Entrance, |c| if c == TILE_ENTRANCE { Some(Node::Entrance) } else { None });
+        assert!(adjacent.is_empty());
+    }
+
+    #[test]
+    fn test_heuristic_admissible() {
+        // Example 4 has too many keys to exhaustively check in reasonable time
+        for i in [1, 2, 3, 5].iter() {
+            let map = Map::from_data_file(&format!("day18_example{}.txt", i));
+            let adjacent = map.find_all_adjacent();
+            let heuristic = Heuristic::new(&adjacent);
+            // Check every state reachable in up to 3 steps
+            let mut states: Vec<SearchState> = vec![(Node::Entrance, [Node::Entrance].iter().cloned().collect())];
+            for _ in 0 .. 3 {
+                let more: Vec<SearchState> = states.iter()
+                    .flat_map(|(last, keys)| adjacent[last].iter()
+                        .filter(move |(_, e)| e.requirements.is_subset(keys))
+                        .map(move |(next, _)| (*next, keys.union(&[*next].iter().cloned().collect()))))
+                    .collect();
+                states.extend(more);
+            }
+            for state in states {
+                let remaining = search(heuristic.nodes, state, adjacent_successors(&adjacent), |_| 0).unwrap();
+                assert!(heuristic.estimate(state) <= remaining,
+                        "example {} state {:?}: {} > {}", i, state, heuristic.estimate(state), remaining);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tree_search_matches_adjacent_search() {
+        for i in 1 ..= 5 {
+            let map = Map::from_data_file(&format!("day18_example{}.txt", i));
+            let adjacent = map.find_all_adjacent();
+            let heuristic = Heuristic::new(&adjacent);
+            let start = (Node::Entrance, [Node::Entrance].iter().cloned().collect());
+            let expected = search(heuristic.nodes, start, adjacent_successors(&adjacent), |state| heuristic.estimate(state));
+            assert!(NodeGraph::from(&map).is_tree(), "example {}", i);
+            assert_eq!(Some(collect_keys(&map)), expected, "example {}", i);
+        }
+    }
+
+    #[test]
+    fn test_split_vaults() {
+        let examples: [(&[&str], usize); 3] = [
+            (&[
+                "#######",
+                "#a.#Cd#",
+                "##...##",
+                "##.@.##",
+                "##...##",
+                "#cB#Ab#",
+                "#######",
+            ], 8),
+            (&[
+                "###############",
+                "#d.ABC.#.....a#",
+                "######...######",
+                "######.@.######",
+                "######...######",
+                "#b.....#.....c#",
+                "###############",
+            ], 24),
+            (&[
+                "#############",
+                "#DcBa.#.GhKl#",
+                "#.###...#I###",
+                "#e#d#.@.#j#k#",
+                "###C#...###J#",
+                "#fEbA.#.FgHi#",
+                "#############",
+            ], 32),
+        ];
+        for (rows, expected) in examples.iter() {
+            let lines: Vec<String> = rows.iter().map(|row| row.to_string()).collect();
+            let vaults = split_vaults(&lines);
+            assert_eq!(vaults.len(), 4);
+            assert_eq!(vaults.iter().map(collect_keys).sum::<usize>(), *expected);
+        }
+    }
+
     #[test]
     fn test_part1() {
-        assert_eq!(part1(), unimplemented!());
+        assert_eq!(part1(), 5262);
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(), unimplemented!());
+        assert_eq!(part2(), 2136);
     }
 }
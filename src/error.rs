@@ -0,0 +1,58 @@
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::num::ParseIntError;
+
+/// Errors that can occur while solving a puzzle
+#[derive(Debug)]
+pub enum AocError {
+    /// Failed to read input data
+    Io(io::Error),
+    /// Input data was malformed
+    Parse(String),
+    /// An Intcode program didn't behave as expected
+    Intcode(String),
+    /// The puzzle input has no solution, according to the approach taken
+    NoSolution(String),
+}
+
+pub type Result<T, E = AocError> = std::result::Result<T, E>;
+
+impl Display for AocError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            AocError::Io(e) => write!(f, "I/O error: {}", e),
+            AocError::Parse(s) => write!(f, "parse error: {}", s),
+            AocError::Intcode(s) => write!(f, "intcode error: {}", s),
+            AocError::NoSolution(s) => write!(f, "no solution: {}", s),
+        }
+    }
+}
+
+impl Error for AocError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AocError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for AocError {
+    fn from(e: io::Error) -> Self {
+        AocError::Io(e)
+    }
+}
+
+impl From<Infallible> for AocError {
+    fn from(e: Infallible) -> Self {
+        match e {}
+    }
+}
+
+impl From<ParseIntError> for AocError {
+    fn from(e: ParseIntError) -> Self {
+        AocError::Parse(e.to_string())
+    }
+}
@@ -1,29 +1,78 @@
 use std::str::FromStr;
-use std::num::ParseIntError;
 use std::collections::VecDeque;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, Write};
 use std::iter::FromIterator;
 use std::iter::repeat_with;
+use std::time::{Duration, Instant};
+use crate::error::{AocError, Result};
 use crate::util;
 
 pub type Word = i64;
 
+#[derive(Clone,Debug,Default,Eq,PartialEq)]
 pub struct Program(Vec<Word>);
 
+impl Program {
+    /// Load a program from a file, which may be wrapped over several lines
+    pub fn from_data_file(filename: &str) -> Result<Program> {
+        let program = Program::from_multiline(&util::read_lines(filename)?.join("\n"))?;
+        if program.0.is_empty() {
+            return Err(AocError::Parse(format!("no program in {}", filename)));
+        }
+        Ok(program)
+    }
+
+    /// Parse a program that may be wrapped over several lines, with or without a trailing comma
+    pub fn from_multiline(s: &str) -> Result<Program> {
+        let code = s
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|x| !x.is_empty())
+            .map(|x| x.parse::<Word>())
+            .collect::<Result<_, _>>()?;
+        Ok(Program(code))
+    }
+
+    /// Load the program on line `n` (counting from 0) of a file with one program per line
+    pub fn from_data_file_nth(filename: &str, n: usize) -> Result<Program> {
+        let mut programs: Vec<Program> = util::read_data(filename)?;
+        if n >= programs.len() {
+            return Err(AocError::Parse(format!("no program {} in {} ({} found)", n, filename, programs.len())));
+        }
+        Ok(programs.swap_remove(n))
+    }
+
+    /// Overwrite the program at each `(address, value)` in `edits`, extending it with zeroes if
+    /// an address is beyond the end
+    pub fn patch(&mut self, edits: &[(usize, Word)]) {
+        for &(addr, value) in edits {
+            if addr >= self.0.len() {
+                self.0.resize(addr + 1, 0);
+            }
+            self.0[addr] = value;
+        }
+    }
+}
+
 impl FromStr for Program {
-    type Err = ParseIntError;
+    type Err = AocError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let code = s.split(",").map(|x| x.parse::<Word>().unwrap()).collect();
-        Ok(Program(code))
+        Ok(Program(util::parse_ints_csv(s)?))
     }
 }
 
+/// Number of steps between checks of the clock in `Emulator::run_timeout()`
+const TIMEOUT_CHECK_INTERVAL: usize = 4096;
+
 const MODE_POSITION: Word = 0;
 const MODE_IMMEDIATE: Word = 1;
 const MODE_RELATIVE: Word = 2;
 
+/// An instruction parameter, with its addressing mode
 #[derive(Debug,Eq,PartialEq)]
-enum Param {
+pub enum Param {
     Position(Word),
     Immediate(Word),
     Relative(Word),
@@ -41,8 +90,9 @@ impl Param {
     }
 }
 
+/// A decoded instruction
 #[derive(Debug,Eq,PartialEq)]
-enum Op {
+pub enum Op {
     Add(Param, Param, Param),
     Mul(Param, Param, Param),
     Read(Param),
@@ -56,7 +106,8 @@ enum Op {
 }
 
 impl Op {
-    fn size(&self) -> Word {
+    /// Get the number of words the instruction occupies, including its parameters
+    pub fn size(&self) -> Word {
         use Op::*;
         match self {
             Add(_, _, _) => 4,
@@ -80,6 +131,15 @@ pub enum State {
     ReadWait,
 }
 
+/// Which way a value passed between the program and the outside world, see `Emulator::io_log()`
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum IoDir {
+    /// Consumed by the program from the input buffer
+    Input,
+    /// Written by the program to the output buffer
+    Output,
+}
+
 #[derive(Clone,Debug)]
 pub struct Emulator {
     memory: Vec<Word>,
@@ -87,6 +147,18 @@ pub struct Emulator {
     sp: Word,
     input_buffer: VecDeque<Word>,
     output_buffer: VecDeque<Word>,
+    logging: bool,
+    io_log: Vec<(IoDir, Word)>,
+}
+
+/// A saved copy of an emulator's state, see `Emulator::snapshot()` and `Emulator::restore()`
+#[derive(Clone,Debug,Eq,PartialEq)]
+pub struct EmulatorSnapshot {
+    memory: Vec<Word>,
+    ip: Word,
+    sp: Word,
+    input_buffer: VecDeque<Word>,
+    output_buffer: VecDeque<Word>,
 }
 
 impl Emulator {
@@ -97,12 +169,18 @@ impl Emulator {
             sp: 0,
             input_buffer: VecDeque::new(),
             output_buffer: VecDeque::new(),
+            logging: false,
+            io_log: Vec::new(),
         }
     }
 
-    pub fn from_data_file(filename: &str) -> Emulator {
-        let programs: Vec<Program> = util::read_data(filename);
-        Emulator::new(&programs[0])
+    pub fn from_data_file(filename: &str) -> Result<Emulator> {
+        Ok(Emulator::new(&Program::from_data_file(filename)?))
+    }
+
+    /// Create an emulator from the program on line `n` (counting from 0) of `filename`
+    pub fn from_data_file_nth(filename: &str, n: usize) -> Result<Emulator> {
+        Ok(Emulator::new(&Program::from_data_file_nth(filename, n)?))
     }
 
     fn make_pointer(&mut self, pos: usize) -> &mut Word {
@@ -125,6 +203,64 @@ impl Emulator {
         self.memory.get(pos).cloned().unwrap_or(0)
     }
 
+    /// `set()` each `(address, value)` in `edits`, e.g. to patch the program before running it
+    pub fn patch(&mut self, edits: &[(Word, Word)]) {
+        for &(addr, value) in edits {
+            self.set(addr, value);
+        }
+    }
+
+    /// Get the current memory contents, e.g. for inspecting whole regions at once
+    ///
+    /// Unlike `get()`, this doesn't include the implicit zeroes beyond the end of memory.
+    pub fn memory(&self) -> &[Word] {
+        &self.memory
+    }
+
+    /// Save the current state, to return to later with `restore()`
+    pub fn snapshot(&self) -> EmulatorSnapshot {
+        EmulatorSnapshot {
+            memory: self.memory.clone(),
+            ip: self.ip,
+            sp: self.sp,
+            input_buffer: self.input_buffer.clone(),
+            output_buffer: self.output_buffer.clone(),
+        }
+    }
+
+    /// Return to the state saved by `snapshot()`, including unconsumed input and unread output
+    pub fn restore(&mut self, snapshot: &EmulatorSnapshot) {
+        self.memory.clone_from(&snapshot.memory);
+        self.ip = snapshot.ip;
+        self.sp = snapshot.sp;
+        self.input_buffer.clone_from(&snapshot.input_buffer);
+        self.output_buffer.clone_from(&snapshot.output_buffer);
+    }
+
+    /// Check if two emulators have the same memory contents, ignoring everything else
+    ///
+    /// Like `get()`, memory beyond the end is treated as zero, so resizing doesn't matter.
+    pub fn memory_eq(&self, other: &Emulator) -> bool {
+        let (shorter, longer) = if self.memory.len() <= other.memory.len() {
+            (&self.memory, &other.memory)
+        } else {
+            (&other.memory, &self.memory)
+        };
+        longer[.. shorter.len()] == shorter[..] && longer[shorter.len() ..].iter().all(|&v| v == 0)
+    }
+
+    /// Hash the full emulator state, e.g. to detect a program stuck in a loop by remembering the
+    /// hashes that have been seen before
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.memory.hash(&mut hasher);
+        self.ip.hash(&mut hasher);
+        self.sp.hash(&mut hasher);
+        self.input_buffer.len().hash(&mut hasher);
+        self.output_buffer.len().hash(&mut hasher);
+        hasher.finish()
+    }
+
     /// Write input value to emulator
     pub fn write(&mut self, v: Word) {
         self.input_buffer.push_back(v);
@@ -135,11 +271,43 @@ impl Emulator {
         self.output_buffer.pop_front()
     }
 
+    /// Start or stop recording every value the program consumes or outputs, see `io_log()`
+    ///
+    /// Off by default. Stopping keeps what was already recorded.
+    pub fn set_logging(&mut self, enabled: bool) {
+        self.logging = enabled;
+    }
+
+    /// Get every value consumed or output by the program while logging was enabled, in order
+    pub fn io_log(&self) -> &[(IoDir, Word)] {
+        &self.io_log
+    }
+
+    /// Get the number of input values waiting to be consumed by the program
+    pub fn input_len(&self) -> usize {
+        self.input_buffer.len()
+    }
+
+    /// Get the number of output values waiting to be read
+    pub fn output_len(&self) -> usize {
+        self.output_buffer.len()
+    }
+
     /// Read all unread output from emulator
     pub fn read_all(&mut self) -> Vec<Word> {
         self.output_buffer.drain(..).collect()
     }
 
+    /// Discard any input that hasn't been consumed by the program yet
+    pub fn clear_input(&mut self) {
+        self.input_buffer.clear();
+    }
+
+    /// Discard any output that hasn't been read yet
+    pub fn clear_output(&mut self) {
+        self.output_buffer.clear();
+    }
+
     /// Write `s` to emulator as ASCII bytes, terminated by a newline
     pub fn write_line(&mut self, s: &str) {
         for b in s.bytes() {
@@ -163,6 +331,66 @@ impl Emulator {
         }
     }
 
+    /// Write `input` as a line of ASCII, run until the program halts or needs more input, and
+    /// return the resulting state along with the ASCII output
+    ///
+    /// The output stops at the first non-ASCII value, which is left to be read along with
+    /// everything after it.
+    pub fn run_ascii(&mut self, input: &str) -> (State, String) {
+        self.write_line(input);
+        let state = self.run();
+        let mut output = String::new();
+        while let Some(&v) = self.output_buffer.front() {
+            if !(0 ..= 127).contains(&v) {
+                break;
+            }
+            self.output_buffer.pop_front();
+            output.push(v as u8 as char);
+        }
+        (state, output)
+    }
+
+    /// Run until the program waits for input, then read one line of ASCII output and check that
+    /// it's the `expected` prompt
+    ///
+    /// On mismatch, or if the program halts instead, returns the line that was actually read
+    /// (empty if there was none).
+    pub fn run_to_prompt(&mut self, expected: &str) -> Result<(), String> {
+        let state = self.run();
+        let line = self.read_line().unwrap_or_default();
+        if state == State::ReadWait && line == expected {
+            Ok(())
+        } else {
+            Err(line)
+        }
+    }
+
+    /// Read lines of ASCII output until a (discarded) blank line or the end of ASCII output, e.g.
+    /// a frame of video output
+    ///
+    /// Any non-ASCII output value is left to be read, along with everything after it.
+    pub fn read_frame(&mut self) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        let mut line = String::new();
+        while let Some(&v) = self.output_buffer.front() {
+            if !(0 ..= 127).contains(&v) {
+                break;
+            }
+            self.output_buffer.pop_front();
+            if v != b'\n' as Word {
+                line.push(v as u8 as char);
+            } else if line.is_empty() {
+                return lines;
+            } else {
+                lines.push(std::mem::take(&mut line));
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+        lines
+    }
+
     fn fetch(&self, pos: Word) -> Op {
         let op = self.get(pos);
         let (modes, opcode) = (op / 100, op % 100);
@@ -234,6 +462,9 @@ impl Emulator {
                 match self.input_buffer.pop_front() {
                     Some(v) => {
                         *self.pointer(a) = v;
+                        if self.logging {
+                            self.io_log.push((IoDir::Input, v));
+                        }
                     },
                     None => {
                         // Don't increment instruction pointer, will re-try on next step()/run()
@@ -242,7 +473,11 @@ impl Emulator {
                 }
             },
             Write(a) => {
-                self.output_buffer.push_back(self.value(a));
+                let v = self.value(a);
+                self.output_buffer.push_back(v);
+                if self.logging {
+                    self.io_log.push((IoDir::Output, v));
+                }
             },
             JumpIfTrue(test, dest) => {
                 if self.value(test) != 0 {
@@ -274,6 +509,20 @@ impl Emulator {
         return State::Continue;
     }
 
+    /// Decode the instruction that will be executed next
+    pub fn next_op(&self) -> Op {
+        self.fetch(self.ip)
+    }
+
+    /// Check if the next instruction reads input, but there's no input to read
+    pub fn needs_input(&self) -> bool {
+        self.input_buffer.is_empty() && matches!(self.fetch(self.ip), Op::Read(_))
+    }
+
+    /// Run until the program halts or needs input, returning `State::Halt` or `State::ReadWait`
+    ///
+    /// If the program is already waiting for input, i.e. `needs_input()` is true, this returns
+    /// `State::ReadWait` immediately, so callers must `write()` input before running again.
     pub fn run(&mut self) -> State {
         loop {
             match self.step() {
@@ -282,11 +531,163 @@ impl Emulator {
             }
         }
     }
+
+    /// Run until the program has written `n` more output values, halts, or needs input
+    ///
+    /// Returns `State::Continue` once `n` values have been written, otherwise `State::Halt` or
+    /// `State::ReadWait` like `run()`.
+    pub fn run_until_output(&mut self, n: usize) -> State {
+        let target = self.output_buffer.len() + n;
+        while self.output_buffer.len() < target {
+            match self.step() {
+                State::Continue => (),
+                state => return state,
+            }
+        }
+        State::Continue
+    }
+
+    /// Run until the program halts or needs input, or until `limit` has passed
+    ///
+    /// Returns `State::Continue` if the time limit was reached, otherwise `State::Halt` or
+    /// `State::ReadWait` like `run()`. The clock is only checked every few thousand steps.
+    pub fn run_timeout(&mut self, limit: Duration) -> State {
+        let deadline = Instant::now() + limit;
+        loop {
+            for _ in 0 .. TIMEOUT_CHECK_INTERVAL {
+                match self.step() {
+                    State::Continue => (),
+                    state => return state,
+                }
+            }
+            if Instant::now() >= deadline {
+                return State::Continue;
+            }
+        }
+    }
+
+    /// Run the program, calling `f` with each complete line of ASCII output as soon as it's written
+    ///
+    /// Stops when the program halts or needs input, returning `State::Halt` or `State::ReadWait`, or
+    /// at the first non-ASCII output value, returning `State::Continue`. Anything not passed to `f`,
+    /// i.e. an incomplete line or the non-ASCII value and what follows it, is left to be read.
+    pub fn for_each_ascii_line(&mut self, mut f: impl FnMut(&str)) -> State {
+        let mut line = String::new();
+        let mut consumed = 0;
+        loop {
+            // Process output already written, before running to produce more
+            if let Some(&v) = self.output_buffer.get(consumed) {
+                if !(0 ..= 127).contains(&v) {
+                    return State::Continue;
+                }
+                consumed += 1;
+                if v == b'\n' as Word {
+                    f(&line);
+                    line.clear();
+                    self.output_buffer.drain(.. consumed);
+                    consumed = 0;
+                } else {
+                    line.push(v as u8 as char);
+                }
+                continue;
+            }
+            match self.run_until_output(1) {
+                State::Continue => (),
+                state => return state,
+            }
+        }
+    }
+
+    /// Run an ASCII program interactively using stdin and stdout, see `run_interactive_with()`
+    pub fn run_interactive(&mut self) -> io::Result<State> {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        self.run_interactive_with(&mut stdin.lock(), &mut stdout.lock())
+    }
+
+    /// Run an ASCII program, writing its output to `output` and writing a line from `input` to
+    /// the program whenever it waits for input
+    ///
+    /// Output values outside of the ASCII range are written as `[n]`. Returns when the program
+    /// halts, or when it waits for input and `input` has reached EOF.
+    pub fn run_interactive_with(&mut self, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<State> {
+        loop {
+            let state = self.run();
+            for v in self.read_all() {
+                if (0 ..= 127).contains(&v) {
+                    write!(output, "{}", v as u8 as char)?;
+                } else {
+                    write!(output, "[{}]", v)?;
+                }
+            }
+            output.flush()?;
+            if state != State::ReadWait {
+                return Ok(state);
+            }
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(state);
+            }
+            self.write_line(line.trim_end_matches(&['\r', '\n'][..]));
+        }
+    }
+}
+
+/// Configure an `Emulator` in one expression, e.g.
+/// `EmulatorBuilder::new().program(&program).patch(1, noun).patch(2, verb).build()`
+#[derive(Clone,Debug,Default)]
+pub struct EmulatorBuilder {
+    program: Program,
+    memory_size: usize,
+    input: Vec<Word>,
+    patches: Vec<(Word, Word)>,
+}
+
+impl EmulatorBuilder {
+    pub fn new() -> EmulatorBuilder {
+        Default::default()
+    }
+
+    pub fn program(mut self, program: &Program) -> Self {
+        self.program = program.clone();
+        self
+    }
+
+    /// Allocate at least `size` words of memory up front (the program is never truncated)
+    pub fn memory_size(mut self, size: usize) -> Self {
+        self.memory_size = size;
+        self
+    }
+
+    /// Add values to the input buffer
+    pub fn input(mut self, values: &[Word]) -> Self {
+        self.input.extend_from_slice(values);
+        self
+    }
+
+    /// Overwrite the program at `addr` with `value`, applied in order after loading the program
+    pub fn patch(mut self, addr: Word, value: Word) -> Self {
+        self.patches.push((addr, value));
+        self
+    }
+
+    pub fn build(self) -> Emulator {
+        let mut emulator = Emulator::new(&self.program);
+        if emulator.len() < self.memory_size {
+            emulator.resize(self.memory_size);
+        }
+        emulator.patch(&self.patches);
+        for v in self.input {
+            emulator.write(v);
+        }
+        emulator
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
 
     macro_rules! assert_match {
         ($a:expr, $b:pat) => {
@@ -317,6 +718,341 @@ mod tests {
         assert_eq!(e.value(&Position(2)), 4);
     }
 
+    #[test]
+    fn test_run_interactive_with() {
+        // Echo every input character back as output, forever
+        let mut e = Emulator::new(&"3,100,4,100,1105,1,0".parse::<Program>().unwrap());
+        let mut input = io::Cursor::new("north\ntake coin\n");
+        let mut output: Vec<u8> = Vec::new();
+        assert_eq!(e.run_interactive_with(&mut input, &mut output).unwrap(), State::ReadWait);
+        assert_eq!(String::from_utf8(output).unwrap(), "north\ntake coin\n");
+
+        // Output non-ASCII values as numbers, and stop when the program halts
+        let mut e = Emulator::new(&"104,72,104,105,104,1000,104,10,99".parse::<Program>().unwrap());
+        let mut output: Vec<u8> = Vec::new();
+        assert_eq!(e.run_interactive_with(&mut io::empty(), &mut output).unwrap(), State::Halt);
+        assert_eq!(String::from_utf8(output).unwrap(), "Hi[1000]\n");
+    }
+
+    #[test]
+    fn test_next_op() {
+        use Op::*;
+        use Param::*;
+        let mut e = Emulator::new(&"1,9,10,3,2,3,11,0,99,30,40,50".parse::<Program>().unwrap());
+        assert_eq!(e.next_op(), Add(Position(9), Position(10), Position(3)));
+        assert_eq!(e.next_op().size(), 4);
+        e.step();
+        assert_eq!(e.next_op(), Mul(Position(3), Position(11), Position(0)));
+        e.step();
+        assert_eq!(e.next_op(), Halt);
+    }
+
+    #[test]
+    fn test_needs_input() {
+        let mut e = Emulator::new(&"3,0,99".parse::<Program>().unwrap());
+        assert!(e.needs_input());
+        assert_eq!(e.run(), State::ReadWait);
+        assert!(e.needs_input());
+        e.write(5);
+        assert!(!e.needs_input());
+        assert_eq!(e.run(), State::Halt);
+        assert!(!e.needs_input());
+    }
+
+    #[test]
+    fn test_read_frame() {
+        let mut e = Emulator::new(&"99".parse::<Program>().unwrap());
+        e.output_buffer.extend("#.#\n.^.\n\nnext\n".bytes().map(|b| b as Word));
+        assert_eq!(e.read_frame(), vec!["#.#", ".^."]);
+        assert_eq!(e.read_line(), Some("next".to_string()));
+        // Stop at non-ASCII output, even without a blank line
+        e.output_buffer.extend("..\n#".bytes().map(|b| b as Word));
+        e.output_buffer.push_back(1234);
+        assert_eq!(e.read_frame(), vec!["..", "#"]);
+        assert_eq!(e.read_all(), vec![1234]);
+        assert!(e.read_frame().is_empty());
+    }
+
+    #[test]
+    fn test_state_hash() {
+        fn first_repeat(program: &str, max_steps: usize) -> Option<usize> {
+            let mut e = Emulator::new(&program.parse::<Program>().unwrap());
+            let mut seen = HashSet::new();
+            for i in 0..max_steps {
+                if !seen.insert(e.state_hash()) {
+                    return Some(i);
+                }
+                e.step();
+            }
+            None
+        }
+        // Toggle a value between 0 and 1 forever
+        assert_eq!(first_repeat("1002,11,-1,11,1001,11,1,11,1105,1,0,0", 100), Some(6));
+        // Increment a counter forever, so never the same state twice
+        assert_eq!(first_repeat("1001,7,1,7,1105,1,0,0", 100), None);
+    }
+
+    #[test]
+    fn test_clear_input_output() {
+        // Echo input to output forever
+        let mut e = Emulator::new(&"3,7,4,7,1105,1,0,0".parse::<Program>().unwrap());
+        e.write(1);
+        e.write(2);
+        e.step();
+        e.clear_input();
+        assert_eq!(e.run(), State::ReadWait);
+        assert_eq!(e.read_all(), vec![1]);
+        e.write(3);
+        e.write(4);
+        assert_eq!(e.run(), State::ReadWait);
+        e.clear_output();
+        assert_eq!(e.read(), None);
+        e.write(5);
+        assert_eq!(e.run(), State::ReadWait);
+        assert_eq!(e.read_all(), vec![5]);
+    }
+
+    #[test]
+    fn test_patch() {
+        let program: Program = "1,9,10,3,2,3,11,0,99,30,40,50".parse().unwrap();
+        let mut patched = Emulator::new(&program);
+        patched.patch(&[(9, 20), (10, 5)]);
+        let mut set = Emulator::new(&program);
+        set.set(9, 20);
+        set.set(10, 5);
+        assert_eq!(patched.memory(), set.memory());
+        patched.run();
+        set.run();
+        assert_eq!(patched.memory(), set.memory());
+        assert_eq!(patched.get(0), (20 + 5) * 50);
+        // Patching the program first is the same as patching the emulator
+        let mut patched_program = program.clone();
+        patched_program.patch(&[(9, 20), (10, 5)]);
+        let mut e = Emulator::new(&patched_program);
+        e.run();
+        assert_eq!(e.memory(), set.memory());
+        // Patching beyond the end extends the program
+        let mut short: Program = "99".parse().unwrap();
+        short.patch(&[(3, 7)]);
+        assert_eq!(short, "99,0,0,7".parse().unwrap());
+    }
+
+    #[test]
+    fn test_builder() {
+        let program = "3,9,1,9,10,0,4,0,99".parse::<Program>().unwrap();
+        let mut manual = Emulator::new(&program);
+        manual.resize(20);
+        manual.set(10, 5);
+        manual.write(7);
+        let mut built = EmulatorBuilder::new()
+            .program(&program)
+            .memory_size(20)
+            .patch(10, 5)
+            .input(&[7])
+            .build();
+        assert_eq!(built.memory(), manual.memory());
+        assert_eq!(built.run(), State::Halt);
+        assert_eq!(manual.run(), State::Halt);
+        assert_eq!(built.read_all(), vec![12]);
+        assert_eq!(manual.read_all(), vec![12]);
+        // Memory size never truncates the program
+        let small = EmulatorBuilder::new().program(&program).memory_size(2).build();
+        assert_eq!(small.len(), 9);
+    }
+
+    #[test]
+    fn test_from_data_file_nth() {
+        // Program 0 outputs whether the input equals 8, program 1 whether it's less than 8
+        let run = |n: usize, input: Word| {
+            let mut e = Emulator::from_data_file_nth("day05_example1.txt", n).unwrap();
+            e.write(input);
+            e.run();
+            e.read_all()
+        };
+        assert_eq!(run(0, 8), vec![1]);
+        assert_eq!(run(0, 7), vec![0]);
+        assert_eq!(run(1, 8), vec![0]);
+        assert_eq!(run(1, 7), vec![1]);
+        assert_eq!(Emulator::from_data_file("day05_example1.txt").unwrap().memory()[2], 8);
+        match Emulator::from_data_file_nth("day05_example1.txt", 2) {
+            Err(AocError::Parse(msg)) => assert_eq!(msg, "no program 2 in day05_example1.txt (2 found)"),
+            other => panic!("expected parse error, got {:?}", other.map(|e| e.memory().to_vec())),
+        }
+    }
+
+    #[test]
+    fn test_run_ascii() {
+        // Echo input to output forever
+        let mut e = Emulator::new(&"3,7,4,7,1105,1,0,0".parse::<Program>().unwrap());
+        assert_eq!(e.run_ascii("hello"), (State::ReadWait, "hello\n".to_string()));
+        assert_eq!(e.run_ascii(""), (State::ReadWait, "\n".to_string()));
+        e.write(1000);
+        assert_eq!(e.run_ascii("world"), (State::ReadWait, String::new()));
+        assert_eq!(e.read(), Some(1000));
+        assert_eq!(e.read_line(), Some("world".to_string()));
+    }
+
+    #[test]
+    fn test_run_to_prompt() {
+        // Prompt "A:", read, prompt "B:", read, halt
+        let program: Program = "104,65,104,58,104,10,3,20,104,66,104,58,104,10,3,20,99".parse().unwrap();
+        let mut e = Emulator::new(&program);
+        assert_eq!(e.run_to_prompt("A:"), Ok(()));
+        // Already waiting, so the prompt has been consumed
+        assert_eq!(e.run_to_prompt("A:"), Err(String::new()));
+        e.write(1);
+        assert_eq!(e.run_to_prompt("C:"), Err("B:".to_string()));
+        e.write(1);
+        assert_eq!(e.run_to_prompt("C:"), Err(String::new()));
+        assert_eq!(e.run(), State::Halt);
+    }
+
+    #[test]
+    fn test_program_from_multiline() {
+        let single = "1,9,10,3,2,3,11,0,99,30,40,50".parse::<Program>().unwrap();
+        assert_eq!(Program::from_multiline("1,9,10,3,\n2,3,11,0,99,30,40,50").unwrap(), single);
+        assert_eq!(Program::from_multiline("1,9,10,3,2,3\r\n11,0,99,30,40,50\n").unwrap(), single);
+        assert_eq!(Program::from_multiline("1,9,10,3,2,3,11,0,99,30,40,50").unwrap(), single);
+        assert!(matches!(Program::from_multiline("1,9,x"), Err(AocError::Parse(_))));
+        assert_eq!(Program::from_data_file("day02_example1.txt").unwrap(), single);
+        let mut e = Emulator::from_data_file("day02_example1.txt").unwrap();
+        e.run();
+        assert_eq!(e.get(0), 3500);
+    }
+
+    #[test]
+    fn test_memory_eq() {
+        let program = Program::from_data_file("day02_example1.txt").unwrap();
+        let mut a = Emulator::new(&program);
+        let mut b = EmulatorBuilder::new().program(&program).memory_size(100).input(&[1, 2, 3]).build();
+        assert!(a.memory_eq(&b));
+        a.run();
+        assert!(!a.memory_eq(&b));
+        assert!(!b.memory_eq(&a));
+        b.run();
+        assert!(a.memory_eq(&b));
+        assert!(b.memory_eq(&a));
+        b.set(99, 1);
+        assert!(!a.memory_eq(&b));
+    }
+
+    #[test]
+    fn test_run_timeout() {
+        // Loop forever
+        let mut e = Emulator::new(&"1105,1,0".parse::<Program>().unwrap());
+        let start = Instant::now();
+        assert_eq!(e.run_timeout(Duration::from_millis(50)), State::Continue);
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(50), "returned early: {:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(5), "returned late: {:?}", elapsed);
+        // Programs that stop by themselves are unaffected
+        let mut e = Emulator::new(&"3,0,99".parse::<Program>().unwrap());
+        assert_eq!(e.run_timeout(Duration::from_secs(5)), State::ReadWait);
+        e.write(1);
+        assert_eq!(e.run_timeout(Duration::from_secs(5)), State::Halt);
+    }
+
+    #[test]
+    fn test_io_log() {
+        let program: Program = "3,0,4,0,99".parse().unwrap();
+        let mut e = Emulator::new(&program);
+        e.set_logging(true);
+        e.write(42);
+        assert_eq!(e.run(), State::Halt);
+        assert_eq!(e.io_log(), &[(IoDir::Input, 42), (IoDir::Output, 42)]);
+        // Nothing is recorded by default
+        let mut e = Emulator::new(&program);
+        e.write(42);
+        e.run();
+        assert_eq!(e.io_log(), &[]);
+    }
+
+    #[test]
+    fn test_buffer_lengths() {
+        // Output 1, 2, 3, then read an input
+        let mut e = Emulator::new(&"104,1,104,2,104,3,3,0,99".parse::<Program>().unwrap());
+        assert_eq!((e.input_len(), e.output_len()), (0, 0));
+        assert_eq!(e.run_until_output(2), State::Continue);
+        assert_eq!(e.output_len(), 2);
+        assert_eq!(e.read(), Some(1));
+        assert_eq!(e.output_len(), 1);
+        assert_eq!(e.run(), State::ReadWait);
+        assert_eq!(e.output_len(), 2);
+        assert_eq!(e.read_all(), vec![2, 3]);
+        assert_eq!(e.output_len(), 0);
+        e.write(7);
+        e.write(8);
+        assert_eq!(e.input_len(), 2);
+        assert_eq!(e.run(), State::Halt);
+        assert_eq!(e.input_len(), 1);
+    }
+
+    #[test]
+    fn test_run_until_output() {
+        // Output 1, 2, then read input and output it, then halt
+        let mut e = Emulator::new(&"104,1,104,2,3,11,4,11,99".parse::<Program>().unwrap());
+        assert_eq!(e.run_until_output(1), State::Continue);
+        assert_eq!(e.read_all(), vec![1]);
+        assert_eq!(e.run_until_output(2), State::ReadWait);
+        assert_eq!(e.read_all(), vec![2]);
+        e.write(3);
+        assert_eq!(e.run_until_output(1), State::Continue);
+        assert_eq!(e.run_until_output(1), State::Halt);
+        assert_eq!(e.read_all(), vec![3]);
+        assert_eq!(e.run_until_output(0), State::Continue);
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        // Add up inputs forever, outputting the running total (using relative mode to move sp too)
+        let mut e = Emulator::new(&"109,1,3,20,1,20,21,21,4,21,1105,1,2".parse::<Program>().unwrap());
+        e.write(5);
+        e.write(7);
+        assert_eq!(e.run_until_output(1), State::Continue);
+        let snapshot = e.snapshot();
+        assert_eq!(e.run(), State::ReadWait);
+        e.write(100);
+        assert_eq!(e.run(), State::ReadWait);
+        assert_eq!(e.read_all(), vec![5, 12, 112]);
+        assert_ne!(e.snapshot(), snapshot);
+        e.restore(&snapshot);
+        assert_eq!(e.snapshot(), snapshot);
+        assert_eq!(e.state_hash(), {
+            let mut fresh = Emulator::new(&Program::default());
+            fresh.restore(&snapshot);
+            fresh.state_hash()
+        });
+        // Carries on exactly as it did the first time
+        assert_eq!(e.run(), State::ReadWait);
+        assert_eq!(e.read_all(), vec![5, 12]);
+    }
+
+    #[test]
+    fn test_for_each_ascii_line() {
+        // Output "ab\n", "c\n", "d" then 500, then "e\n", then halt
+        let mut e = Emulator::new(&"104,97,104,98,104,10,104,99,104,10,104,100,104,500,104,101,104,10,99".parse::<Program>().unwrap());
+        let mut lines: Vec<String> = Vec::new();
+        assert_eq!(e.for_each_ascii_line(|line| lines.push(line.to_string())), State::Continue);
+        assert_eq!(lines, vec!["ab", "c"]);
+        // The incomplete line and the non-ASCII value are left to be read
+        assert_eq!(e.read(), Some(100));
+        assert_eq!(e.read(), Some(500));
+        let mut count = 0;
+        assert_eq!(e.for_each_ascii_line(|line| { assert_eq!(line, "e"); count += 1; }), State::Halt);
+        assert_eq!(count, 1);
+        assert_eq!(e.read(), None);
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut e = Emulator::new(&"1,9,10,3,2,3,11,0,99,30,40,50".parse::<Program>().unwrap());
+        assert_eq!(e.memory(), [1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        assert_eq!(e.run(), State::Halt);
+        assert_eq!(e.memory()[.. 4], [3500, 9, 10, 70]);
+        e.set(15, 1);
+        assert_eq!(e.memory().len(), 16);
+    }
+
     #[test]
     fn test_program_day02_1() {
         let mut e = Emulator::new(&"1,9,10,3,2,3,11,0,99,30,40,50".parse::<Program>().unwrap());
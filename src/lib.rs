@@ -1,8 +1,8 @@
-#[macro_use] extern crate itertools;
-
 #[macro_use]
 pub mod util;
+pub mod error;
 pub mod intcode;
+pub mod day;
 pub mod day01;
 pub mod day02;
 pub mod day03;
@@ -1,51 +1,31 @@
+use std::env;
+use std::process;
 use std::time::Instant;
 
-use advent_of_code_2019::*;
+use advent_of_code_2019::day;
 
-macro_rules! run {
-    ($l:expr) => {
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let selected = day::select(&args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        eprintln!("usage: advent_of_code_2019 [DAY [PART]]");
+        process::exit(2);
+    });
+    let mut failed = false;
+    for (name, part) in selected {
         let start = Instant::now();
-        let result = $l;
+        let result = part();
         let elapsed = Instant::now().duration_since(start);
-        println!("{}: {} ({:?})", stringify!($l), result, elapsed);
+        match result {
+            Ok(result) => println!("{}: {} ({:?})", name, result, elapsed),
+            Err(e) => {
+                eprintln!("{}: {}", name, e);
+                failed = true;
+            },
+        }
+    }
+    if failed {
+        process::exit(1);
     }
-}
-
-fn main() {
-    run!(day01::part1());
-    run!(day01::part2());
-    run!(day02::part1());
-    run!(day02::part2());
-    run!(day03::part1());
-    run!(day03::part2());
-    run!(day04::part1());
-    run!(day04::part2());
-    run!(day05::part1());
-    run!(day05::part2());
-    run!(day06::part1());
-    run!(day06::part2());
-    run!(day07::part1());
-    run!(day07::part2());
-    run!(day08::part1());
-    run!(day08::part2());
-    run!(day09::part1());
-    run!(day09::part2());
-    run!(day10::part1());
-    run!(day10::part2());
-    run!(day11::part1());
-    run!(day11::part2());
-    run!(day12::part1());
-    run!(day12::part2());
-    run!(day13::part1());
-    run!(day13::part2());
-    run!(day14::part1());
-    run!(day14::part2());
-    run!(day15::part1());
-    run!(day15::part2());
-    run!(day16::part1());
-    run!(day16::part2());
-    run!(day17::part1());
-    run!(day17::part2());
-    run!(day18::part1());
-    run!(day18::part2());
 }
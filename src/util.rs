@@ -1,30 +1,108 @@
-use std::cmp::{max, min};
-use std::fmt::Debug;
+use std::cmp::{max, min, Reverse};
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
 use std::fs::File;
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, Write};
 use std::ops;
 use std::path::Path;
 use std::str::FromStr;
+use num::Integer;
+use crate::error::{AocError, Result};
 
-fn open_data(filename: &str) -> io::BufReader<File>{
+/// Get the contents of `data/<filename>` as compiled into the binary, if the `embedded` feature
+/// is enabled
+#[cfg(feature = "embedded")]
+pub fn read_embedded(filename: &str) -> Option<&'static str> {
+    const FILES: &[(&str, &str)] = include!(concat!(env!("OUT_DIR"), "/embedded.rs"));
+    FILES.iter().find(|(name, _)| *name == filename).map(|(_, data)| *data)
+}
+
+#[cfg(not(feature = "embedded"))]
+pub fn read_embedded(_filename: &str) -> Option<&'static str> {
+    None
+}
+
+/// Open `data/<filename>`, falling back to the embedded copy if it's not found on disk
+fn open_data(filename: &str) -> io::Result<Box<dyn BufRead>> {
     let path = Path::new("data").join(filename);
-    let file = File::open(path).unwrap();
-    io::BufReader::new(file)
+    match File::open(path) {
+        Ok(file) => Ok(Box::new(io::BufReader::new(file))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => match read_embedded(filename) {
+            Some(data) => Ok(Box::new(data.as_bytes())),
+            None => Err(e),
+        },
+        Err(e) => Err(e),
+    }
 }
 
-pub fn read_lines(filename: &str) -> Vec<String> {
-    let reader = open_data(filename);
-    reader.lines().map(|x| x.unwrap()).collect()
+pub fn read_lines(filename: &str) -> Result<Vec<String>> {
+    let reader = open_data(filename)?;
+    Ok(reader.lines().collect::<io::Result<_>>()?)
 }
 
-pub fn read_data<T>(filename: &str) -> Vec<T>
-    where T: FromStr, <T as FromStr>::Err: Debug {
-    let reader = open_data(filename);
+pub fn read_data<T>(filename: &str) -> Result<Vec<T>>
+    where T: FromStr, <T as FromStr>::Err: Into<AocError> {
+    let reader = open_data(filename)?;
     let mut data: Vec<T> = Vec::new();
     for line in reader.lines() {
-        data.push(line.unwrap().parse::<T>().unwrap())
+        data.push(line?.parse::<T>().map_err(Into::into)?)
     }
-    data
+    Ok(data)
+}
+
+/// Read a file containing a single value on its first line
+pub fn read_one<T>(filename: &str) -> Result<T>
+    where T: FromStr, <T as FromStr>::Err: Into<AocError> {
+    let reader = open_data(filename)?;
+    match reader.lines().next() {
+        Some(line) => line?.parse::<T>().map_err(Into::into),
+        None => Err(AocError::Parse(format!("{} is empty", filename))),
+    }
+}
+
+/// Parse a comma-separated list of integers, ignoring whitespace around each one
+pub fn parse_ints_csv(s: &str) -> Result<Vec<i64>> {
+    Ok(s.split(',').map(|x| x.trim().parse::<i64>()).collect::<Result<_, _>>()?)
+}
+
+/// Read the first line of a file as a comma-separated list of integers, see `parse_ints_csv()`
+pub fn read_ints_csv(filename: &str) -> Result<Vec<i64>> {
+    parse_ints_csv(&read_one::<String>(filename)?)
+}
+
+/// Get the lowest common multiple of `a` and `b`
+pub fn lcm(a: i64, b: i64) -> i64 {
+    a.lcm(&b)
+}
+
+/// Get the lowest common multiple of every value, or 1 if there are none
+pub fn lcm_all(values: &[i64]) -> i64 {
+    values.iter().fold(1, |a, &b| lcm(a, b))
+}
+
+/// Get the decimal digits of `n`, most significant first, zero-padded to at least `width` digits
+pub fn to_digits(n: u64, width: usize) -> Vec<u8> {
+    let mut digits: Vec<u8> = Vec::with_capacity(width);
+    let mut n = n;
+    while n > 0 || digits.len() < width.max(1) {
+        digits.push((n % 10) as u8);
+        n /= 10;
+    }
+    digits.reverse();
+    digits
+}
+
+/// Parse a string of decimal digits into the value of each digit
+pub fn from_digits(s: &str) -> Result<Vec<u8>> {
+    s.chars()
+        .map(|c| c.to_digit(10).map(|d| d as u8).ok_or_else(|| AocError::Parse(format!("invalid digit {:?}", c))))
+        .collect()
+}
+
+/// Write each digit one after the other, e.g. `[0, 1, 2]` becomes `"012"`
+pub fn digits_to_string<T: Display>(digits: &[T]) -> String {
+    digits.iter().map(|d| d.to_string()).collect()
 }
 
 macro_rules! vector {
@@ -43,6 +121,16 @@ impl Vector2D {
         self.x.abs() + self.y.abs()
     }
 
+    /// Get the number of orthogonal steps between two points
+    pub fn manhattan_distance(&self, other: &Point2D) -> i32 {
+        (*self - *other).manhattan_length()
+    }
+
+    /// Get the number of steps between two points, allowing diagonal steps
+    pub fn chebyshev_distance(&self, other: &Point2D) -> i32 {
+        max((self.x - other.x).abs(), (self.y - other.y).abs())
+    }
+
     pub fn to_unit_vector(&self) -> Vector2D {
         // Use GCD implementation copied from https://doc.rust-lang.org/std/ops/trait.Div.html
         let mut x = self.x.abs();
@@ -52,6 +140,10 @@ impl Vector2D {
             y = x % y;
             x = t;
         }
+        // The zero vector has no direction, and a GCD of 0
+        if x == 0 {
+            return vector!(0, 0);
+        }
         Vector2D{x: self.x / x, y: self.y / x}
     }
 
@@ -68,6 +160,11 @@ impl Vector2D {
             y: max(self.y, other.y),
         }
     }
+
+    /// Extend into 3 dimensions with the given `z`
+    pub fn with_z(&self, z: i32) -> Vector3D {
+        vector!(self.x, self.y, z)
+    }
 }
 
 impl ops::Add<Vector2D> for Vector2D {
@@ -129,6 +226,11 @@ impl Vector3D {
         self.x.abs() + self.y.abs() + self.z.abs()
     }
 
+    /// Get the number of orthogonal steps between two points
+    pub fn manhattan_distance(&self, other: &Point3D) -> i32 {
+        (*self - *other).manhattan_length()
+    }
+
     pub fn signum(&self) -> Vector3D {
         Vector3D {
             x: self.x.signum(),
@@ -136,6 +238,17 @@ impl Vector3D {
             z: self.z.signum(),
         }
     }
+
+    /// Project onto the XY plane, dropping `z`
+    pub fn xy(&self) -> Vector2D {
+        vector!(self.x, self.y)
+    }
+}
+
+impl From<Vector2D> for Vector3D {
+    fn from(v: Vector2D) -> Self {
+        v.with_z(0)
+    }
 }
 
 impl ops::Add<Vector3D> for Vector3D {
@@ -200,6 +313,16 @@ impl BoundingBox2D {
         }
     }
 
+    /// Create the smallest bounding box containing every point, or `None` if there are no points
+    pub fn from_points(points: impl IntoIterator<Item=Point2D>) -> Option<BoundingBox2D> {
+        let mut points = points.into_iter();
+        let mut bbox = BoundingBox2D::new(&points.next()?);
+        for p in points {
+            bbox.include(&p);
+        }
+        Some(bbox)
+    }
+
     pub fn include(&mut self, point: &Point2D) {
         self.min = self.min.min(point);
         self.max = self.max.max(point);
@@ -212,6 +335,282 @@ impl BoundingBox2D {
     pub fn iter(&self) -> impl Iterator<Item=Point2D> + '_ {
         (self.min.y ..= self.max.y).flat_map(move |y| (self.min.x ..= self.max.x).map(move |x| point!(x, y)))
     }
+
+    /// Iterate over the rows of the bounding box from top to bottom, each as an iterator over its
+    /// points from left to right
+    pub fn iter_rows(&self) -> impl Iterator<Item=impl Iterator<Item=Point2D>> {
+        let (min, max) = (self.min, self.max);
+        (min.y ..= max.y).map(move |y| (min.x ..= max.x).map(move |x| point!(x, y)))
+    }
+}
+
+/// A rectangular grid of values stored row-major, with `bbox.min` at the top left (usually `(0, 0)`)
+#[derive(Clone,Debug)]
+pub struct Grid2D<T> {
+    pub data: Vec<T>,
+    pub width: usize,
+    pub height: usize,
+    pub bbox: BoundingBox2D,
+}
+
+impl<T> Grid2D<T> {
+    /// Get the offset of `p` in `data`, if it's inside the grid
+    fn offset(&self, p: &Point2D) -> Option<usize> {
+        if self.bbox.contains(p) {
+            Some((p.y - self.bbox.min.y) as usize * self.width + (p.x - self.bbox.min.x) as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, p: &Point2D) -> Option<&T> {
+        self.offset(p).map(|i| &self.data[i])
+    }
+
+    pub fn get_mut(&mut self, p: &Point2D) -> Option<&mut T> {
+        self.offset(p).map(move |i| &mut self.data[i])
+    }
+
+    fn offset_or_panic(&self, p: &Point2D) -> usize {
+        self.offset(p).unwrap_or_else(|| panic!("{:?} is outside grid bounds {:?} ..= {:?}", p, self.bbox.min, self.bbox.max))
+    }
+}
+
+/// Panics if the point is outside the grid, see `get()` for a non-panicking alternative
+impl<T> ops::Index<Point2D> for Grid2D<T> {
+    type Output = T;
+
+    fn index(&self, p: Point2D) -> &Self::Output {
+        &self.data[self.offset_or_panic(&p)]
+    }
+}
+
+/// Panics if the point is outside the grid, see `get_mut()` for a non-panicking alternative
+impl<T> ops::IndexMut<Point2D> for Grid2D<T> {
+    fn index_mut(&mut self, p: Point2D) -> &mut Self::Output {
+        let i = self.offset_or_panic(&p);
+        &mut self.data[i]
+    }
+}
+
+impl Grid2D<char> {
+    /// Create a grid from rows of characters, ignoring trailing empty lines
+    ///
+    /// Returns an error if there are no rows or if the rows aren't all the same width.
+    pub fn from_lines(lines: &[String]) -> Result<Grid2D<char>> {
+        let end = lines.iter().rposition(|line| !line.is_empty()).map_or(0, |i| i + 1);
+        let lines = &lines[.. end];
+        let width = lines.first().ok_or_else(|| AocError::Parse("empty grid".to_string()))?.chars().count();
+        let mut data = Vec::with_capacity(width * lines.len());
+        for (y, line) in lines.iter().enumerate() {
+            let row_width = line.chars().count();
+            if row_width != width {
+                return Err(AocError::Parse(format!("grid row {} has width {}, expected {}", y, row_width, width)));
+            }
+            data.extend(line.chars());
+        }
+        let height = lines.len();
+        let bbox = BoundingBox2D::from_points(vec![point!(0, 0), point!(width as i32 - 1, height as i32 - 1)]).unwrap();
+        Ok(Grid2D { data, width, height, bbox })
+    }
+}
+
+/// Find the cells that differ between two frames of rows of characters, as `(position, before,
+/// after)` in row-major order
+///
+/// Frames don't need to be the same size: cells beyond the end of a row or frame count as `' '`.
+pub fn diff_grids(a: &[String], b: &[String]) -> Vec<(Point2D, char, char)> {
+    let a: Vec<Vec<char>> = a.iter().map(|row| row.chars().collect()).collect();
+    let b: Vec<Vec<char>> = b.iter().map(|row| row.chars().collect()).collect();
+    let cell = |rows: &[Vec<char>], x: usize, y: usize| rows.get(y).and_then(|row| row.get(x)).cloned().unwrap_or(' ');
+    let mut changes: Vec<(Point2D, char, char)> = Vec::new();
+    for y in 0 .. max(a.len(), b.len()) {
+        let width = max(a.get(y).map_or(0, Vec::len), b.get(y).map_or(0, Vec::len));
+        for x in 0 .. width {
+            let (before, after) = (cell(&a, x, y), cell(&b, x, y));
+            if before != after {
+                changes.push((point!(x as i32, y as i32), before, after));
+            }
+        }
+    }
+    changes
+}
+
+/// A sparse 2D canvas, tracking the bounding box of every point that has been set
+#[derive(Clone,Debug)]
+pub struct Canvas<T> {
+    data: HashMap<Point2D, T>,
+    bbox: Option<BoundingBox2D>,
+}
+
+impl<T> Canvas<T> {
+    pub fn new() -> Canvas<T> {
+        Canvas { data: HashMap::new(), bbox: None }
+    }
+
+    pub fn set(&mut self, p: Point2D, v: T) {
+        match self.bbox.as_mut() {
+            Some(bbox) => bbox.include(&p),
+            None => self.bbox = Some(BoundingBox2D::new(&p)),
+        }
+        self.data.insert(p, v);
+    }
+
+    pub fn get(&self, p: &Point2D) -> Option<&T> {
+        self.data.get(p)
+    }
+
+    /// Get the number of points that have been set
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item=&T> {
+        self.data.values()
+    }
+
+    /// Iterate over every point that has been set and its value, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item=(&Point2D, &T)> {
+        self.data.iter()
+    }
+
+    /// Get the bounding box of every point that has been set, or `None` if nothing has been set
+    pub fn bbox(&self) -> Option<&BoundingBox2D> {
+        self.bbox.as_ref()
+    }
+
+    /// Render the bounding box as rows of characters, using `f` to choose the character for the
+    /// value at each point
+    pub fn render<F: Fn(Option<&T>) -> char>(&self, f: F) -> Vec<String> {
+        match &self.bbox {
+            None => Vec::new(),
+            Some(bbox) => bbox
+                .iter_rows()
+                .map(|row| row.map(|p| f(self.get(&p))).collect())
+                .collect(),
+        }
+    }
+}
+
+impl<T> Default for Canvas<T> {
+    fn default() -> Self {
+        Canvas::new()
+    }
+}
+
+/// Which of the surrounding positions on a grid count as neighbours
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Connectivity {
+    /// North, east, south and west
+    Four,
+    /// Diagonals as well, i.e. also north-east, south-east, south-west and north-west
+    Eight,
+}
+
+const NEIGHBOURS: [Vector2D; 8] = [
+    vector!(0, -1), vector!(1, 0), vector!(0, 1), vector!(-1, 0),
+    vector!(1, -1), vector!(1, 1), vector!(-1, 1), vector!(-1, -1),
+];
+
+impl Connectivity {
+    /// Get the offsets to each neighbour, clockwise from north (orthogonal before diagonal)
+    pub fn offsets(self) -> &'static [Vector2D] {
+        match self {
+            Connectivity::Four => &NEIGHBOURS[.. 4],
+            Connectivity::Eight => &NEIGHBOURS[..],
+        }
+    }
+
+    pub fn neighbours(self, p: Point2D) -> impl Iterator<Item=Point2D> {
+        self.offsets().iter().map(move |&offset| p + offset)
+    }
+}
+
+/// Find every position reachable from `start` through positions where `passable` is true, and
+/// the number of steps to reach each one
+///
+/// `start` is always included, with distance 0, even if it isn't passable itself.
+pub fn flood_fill<F>(start: Point2D, connectivity: Connectivity, passable: F) -> HashMap<Point2D, usize>
+    where F: Fn(&Point2D) -> bool
+{
+    let mut distances: HashMap<Point2D, usize> = HashMap::new();
+    let mut queue: VecDeque<(Point2D, usize)> = VecDeque::new();
+    distances.insert(start, 0);
+    queue.push_back((start, 0));
+    while let Some((p, distance)) = queue.pop_front() {
+        for next in connectivity.neighbours(p) {
+            if !distances.contains_key(&next) && passable(&next) {
+                distances.insert(next, distance + 1);
+                queue.push_back((next, distance + 1));
+            }
+        }
+    }
+    distances
+}
+
+/// Find the number of steps from `start` to every position reachable through positions where
+/// `passable` is true, see `flood_fill()`, as a dense grid covering every reachable position
+///
+/// Positions in the grid that can't be reached, e.g. walls, are `None`.
+pub fn distance_grid<F>(start: Point2D, passable: F) -> Grid2D<Option<usize>>
+    where F: Fn(&Point2D) -> bool
+{
+    let distances = flood_fill(start, Connectivity::Four, passable);
+    let bbox = BoundingBox2D::from_points(distances.keys().cloned()).unwrap();
+    let width = (bbox.max.x - bbox.min.x + 1) as usize;
+    let height = (bbox.max.y - bbox.min.y + 1) as usize;
+    let data = bbox.iter().map(|p| distances.get(&p).cloned()).collect();
+    Grid2D { data, width, height, bbox }
+}
+
+/// Find the shortest orthogonal path from `start` to `goal` through cells of `grid` where
+/// `passable` is true, using A* search with a Manhattan distance heuristic
+///
+/// Returns the path, including both `start` and `goal`, and its length in steps, or `None` if
+/// `goal` can't be reached.
+pub fn astar_grid<T, F>(grid: &Grid2D<T>, start: Point2D, goal: Point2D, passable: F) -> Option<(Vec<Point2D>, usize)>
+    where F: Fn(&T) -> bool
+{
+    let heuristic = |p: &Point2D| p.manhattan_distance(&goal) as usize;
+    let mut came_from: HashMap<Point2D, Point2D> = HashMap::new();
+    let mut costs: HashMap<Point2D, usize> = HashMap::new();
+    let mut queue: BinaryHeap<Reverse<(usize, usize, i32, i32)>> = BinaryHeap::new();
+    costs.insert(start, 0);
+    queue.push(Reverse((heuristic(&start), 0, start.y, start.x)));
+    while let Some(Reverse((_, cost, y, x))) = queue.pop() {
+        let p = point!(x, y);
+        if p == goal {
+            let mut path = vec![goal];
+            while let Some(prev) = came_from.get(path.last().unwrap()) {
+                path.push(*prev);
+            }
+            path.reverse();
+            return Some((path, cost));
+        }
+        // Skip positions that were already reached more cheaply
+        if costs[&p] < cost {
+            continue;
+        }
+        for next in Connectivity::Four.neighbours(p) {
+            if !matches!(grid.get(&next), Some(tile) if passable(tile)) {
+                continue;
+            }
+            let improved = match costs.get(&next) {
+                Some(&best) => cost + 1 < best,
+                None => true,
+            };
+            if improved {
+                costs.insert(next, cost + 1);
+                came_from.insert(next, p);
+                queue.push(Reverse((cost + 1 + heuristic(&next), cost + 1, next.y, next.x)));
+            }
+        }
+    }
+    None
 }
 
 #[derive(Clone,Copy,Debug,Eq,Hash,PartialEq)]
@@ -287,6 +686,127 @@ impl Line2D {
     }
 }
 
+/// Repeatedly apply `step` to a state, remembering every state seen, to find where it repeats
+pub struct CycleDetector<S, F> {
+    state: S,
+    step: F,
+    history: HashMap<S, usize>,
+}
+
+impl<S, F> CycleDetector<S, F> where S: Hash + Eq + Clone, F: FnMut(&S) -> S {
+    pub fn new(initial: S, step: F) -> CycleDetector<S, F> {
+        CycleDetector { state: initial, step, history: HashMap::new() }
+    }
+
+    /// Get the current state, i.e. the first repeated state once a cycle has been found
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// Step until a state repeats, up to `max_steps` steps
+    ///
+    /// Returns `Some((mu, lambda))` if the state after `mu` steps recurs every `lambda` steps,
+    /// e.g. a fixed point has `lambda == 1`, or `None` if no repeat was found.
+    pub fn find_cycle(&mut self, max_steps: usize) -> Option<(usize, usize)> {
+        self.history.clear();
+        self.history.insert(self.state.clone(), 0);
+        for i in 1 ..= max_steps {
+            self.state = (self.step)(&self.state);
+            if let Some(&mu) = self.history.get(&self.state) {
+                return Some((mu, i - mu));
+            } else {
+                self.history.insert(self.state.clone(), i);
+            }
+        }
+        None
+    }
+}
+
+/// Height of the letters rendered by AoC puzzles, see `ocr_letters()`
+const OCR_HEIGHT: usize = 6;
+/// Width of the letters rendered by AoC puzzles, excluding the blank column between letters
+const OCR_WIDTH: usize = 4;
+
+/// The known AoC letter shapes, as rows of `X`/space pixels
+const OCR_FONT: [(char, [&str; OCR_HEIGHT]); 17] = [
+    ('A', [" XX ", "X  X", "X  X", "XXXX", "X  X", "X  X"]),
+    ('B', ["XXX ", "X  X", "XXX ", "X  X", "X  X", "XXX "]),
+    ('C', [" XX ", "X  X", "X   ", "X   ", "X  X", " XX "]),
+    ('E', ["XXXX", "X   ", "XXX ", "X   ", "X   ", "XXXX"]),
+    ('F', ["XXXX", "X   ", "XXX ", "X   ", "X   ", "X   "]),
+    ('G', [" XX ", "X  X", "X   ", "X XX", "X  X", " XXX"]),
+    ('H', ["X  X", "X  X", "XXXX", "X  X", "X  X", "X  X"]),
+    ('I', [" XXX", "  X ", "  X ", "  X ", "  X ", " XXX"]),
+    ('J', ["  XX", "   X", "   X", "   X", "X  X", " XX "]),
+    ('K', ["X  X", "X X ", "XX  ", "X X ", "X X ", "X  X"]),
+    ('L', ["X   ", "X   ", "X   ", "X   ", "X   ", "XXXX"]),
+    ('O', [" XX ", "X  X", "X  X", "X  X", "X  X", " XX "]),
+    ('P', ["XXX ", "X  X", "X  X", "XXX ", "X   ", "X   "]),
+    ('R', ["XXX ", "X  X", "X  X", "XXX ", "X X ", "X  X"]),
+    ('S', [" XXX", "X   ", "X   ", " XX ", "   X", "XXX "]),
+    ('U', ["X  X", "X  X", "X  X", "X  X", "X  X", " XX "]),
+    ('Z', ["XXXX", "   X", "  X ", " X  ", "X   ", "XXXX"]),
+];
+
+/// Decode capital letters rendered as rows of `X`/space pixels
+///
+/// Leading blank columns are skipped, then the image is split into 5-column cells (a 4-column
+/// glyph followed by a blank column). Each glyph is matched against the AoC letter set, with `?`
+/// for anything unrecognised.
+pub fn ocr_letters(pixels: &[String]) -> String {
+    assert_eq!(pixels.len(), OCR_HEIGHT, "expected {} rows of pixels", OCR_HEIGHT);
+    let rows: Vec<&[u8]> = pixels.iter().map(|row| row.as_bytes()).collect();
+    let width = rows.iter().map(|row| row.len()).min().unwrap_or(0);
+    let is_blank = |x: usize| rows.iter().all(|row| row[x] != b'X');
+    let mut x = (0 .. width).find(|&x| !is_blank(x)).unwrap_or(width);
+    let mut letters = String::new();
+    while x < width && !(x .. width).all(is_blank) {
+        let glyph: Vec<&[u8]> = rows.iter().map(|row| &row[x .. min(x + OCR_WIDTH, width)]).collect();
+        let letter = OCR_FONT.iter()
+            .find(|(_, shape)| shape.iter().zip(glyph.iter()).all(|(a, b)| a.as_bytes() == *b))
+            .map(|(c, _)| *c)
+            .unwrap_or('?');
+        letters.push(letter);
+        x += OCR_WIDTH + 1;
+    }
+    letters
+}
+
+/// Encode rows of `X`/space pixels as a binary PPM (P6) image
+///
+/// Each character becomes a `scale` x `scale` block of white (`X`) or black (anything else).
+fn encode_ppm(pixels: &[String], scale: usize) -> Vec<u8> {
+    let width = pixels.iter().map(|row| row.len()).max().unwrap_or(0) * scale;
+    let height = pixels.len() * scale;
+    let mut data = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    for row in pixels {
+        let mut line: Vec<u8> = Vec::with_capacity(width * 3);
+        for c in row.bytes() {
+            let value = if c == b'X' { 255 } else { 0 };
+            line.extend(std::iter::repeat_n(value, scale * 3));
+        }
+        line.resize(width * 3, 0);
+        for _ in 0 .. scale {
+            data.extend_from_slice(&line);
+        }
+    }
+    data
+}
+
+/// Write rows of `X`/space pixels to `path` as a PPM image, see `encode_ppm()`
+pub fn write_ppm<P: AsRef<Path>>(path: P, pixels: &[String], scale: usize) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(&encode_ppm(pixels, scale))
+}
+
+/// Write rows of `X`/space pixels to `<$AOC_PPM_DIR>/<name>.ppm`, if `AOC_PPM_DIR` is set
+pub fn dump_ppm(name: &str, pixels: &[String]) -> io::Result<()> {
+    match std::env::var_os("AOC_PPM_DIR") {
+        Some(dir) => write_ppm(Path::new(&dir).join(format!("{}.ppm", name)), pixels, 8),
+        None => Ok(()),
+    }
+}
+
 macro_rules! deref {
     ($outer:ty, $inner:ty) => {
         impl Deref for $outer {
@@ -309,6 +829,50 @@ macro_rules! deref {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_lcm() {
+        assert_eq!(lcm(4, 6), 12);
+        assert_eq!(lcm(7, 5), 35);
+        assert_eq!(lcm_all(&[4, 6, 9]), 36);
+        assert_eq!(lcm_all(&[18, 28, 44]), 2772);
+        assert_eq!(lcm_all(&[]), 1);
+    }
+
+    #[test]
+    fn test_digits() {
+        assert_eq!(to_digits(123, 6), vec![0, 0, 0, 1, 2, 3]);
+        assert_eq!(to_digits(123456, 3), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(to_digits(0, 0), vec![0]);
+        assert_eq!(to_digits(0, 2), vec![0, 0]);
+        assert_eq!(digits_to_string(&to_digits(123, 6)), "000123");
+        assert_eq!(from_digits("000123").unwrap(), to_digits(123, 6));
+        assert!(matches!(from_digits("12a"), Err(AocError::Parse(_))));
+    }
+
+    #[test]
+    fn test_distances() {
+        let (a, b) = (point!(1, -2), point!(-4, 1));
+        assert_eq!(a.manhattan_distance(&b), 8);
+        assert_eq!(b.manhattan_distance(&a), 8);
+        assert_eq!(a.chebyshev_distance(&b), 5);
+        assert_eq!(b.chebyshev_distance(&a), 5);
+        assert_eq!(a.manhattan_distance(&a), 0);
+        assert_eq!(a.chebyshev_distance(&a), 0);
+        assert_eq!(point!(1, -2, 3).manhattan_distance(&point!(-4, 1, 3)), 8);
+        assert_eq!(point!(0, 0, 0).manhattan_distance(&point!(1, 1, -1)), 3);
+    }
+
+    #[test]
+    fn test_vector_2d_3d_conversion() {
+        let v = vector!(3, -4);
+        assert_eq!(v.with_z(5), vector!(3, -4, 5));
+        assert_eq!(v.with_z(5).xy(), v);
+        assert_eq!(vector!(1, 2, 3).xy(), vector!(1, 2));
+        assert_eq!(Vector3D::from(v), vector!(3, -4, 0));
+        let w: Vector3D = v.into();
+        assert_eq!(w.xy(), v);
+    }
+
     #[test]
     fn test_point2d_to_unit_vector() {
         // One coordinate is prime, so no division
@@ -322,5 +886,292 @@ mod tests {
         assert_eq!(Vector2D{x: 12, y: -16}.to_unit_vector(), Vector2D{x: 3, y: -4});
         assert_eq!(Vector2D{x: -12, y: 16}.to_unit_vector(), Vector2D{x: -3, y: 4});
         assert_eq!(Vector2D{x: -12, y: -16}.to_unit_vector(), Vector2D{x: -3, y: -4});
+        // Zero vector stays as it is
+        assert_eq!(vector!(0, 0).to_unit_vector(), vector!(0, 0));
+        assert_eq!(vector!(0, 5).to_unit_vector(), vector!(0, 1));
+        assert_eq!(vector!(-5, 0).to_unit_vector(), vector!(-1, 0));
+    }
+
+    #[test]
+    fn test_read_ints_csv() {
+        assert_eq!(read_ints_csv("util_ints_csv.txt").unwrap(), vec![1, -2, 3]);
+        assert_eq!(parse_ints_csv("4").unwrap(), vec![4]);
+        assert!(matches!(parse_ints_csv("1,,2"), Err(AocError::Parse(_))));
+        assert!(matches!(parse_ints_csv("1,x"), Err(AocError::Parse(_))));
+        assert!(matches!(read_ints_csv("missing.txt"), Err(AocError::Io(_))));
+    }
+
+    #[test]
+    fn test_read_one() {
+        #[derive(Debug, PartialEq)]
+        struct Ints(Vec<i64>);
+
+        impl FromStr for Ints {
+            type Err = AocError;
+
+            fn from_str(s: &str) -> Result<Self> {
+                parse_ints_csv(s).map(Ints)
+            }
+        }
+
+        assert_eq!(read_one::<Ints>("util_ints_csv.txt").unwrap(), Ints(vec![1, -2, 3]));
+        assert_eq!(read_one::<String>("util_ints_csv.txt").unwrap(), "1, -2 , 3");
+        assert!(matches!(read_one::<i32>("util_ints_csv.txt"), Err(AocError::Parse(_))));
+        match read_one::<String>("util_empty.txt") {
+            Err(AocError::Parse(s)) => assert_eq!(s, "util_empty.txt is empty"),
+            other => panic!("expected parse error, got {:?}", other),
+        }
+        assert!(matches!(read_one::<String>("missing.txt"), Err(AocError::Io(_))));
+    }
+
+    #[test]
+    fn test_read_missing_file() {
+        assert!(matches!(read_lines("missing.txt"), Err(AocError::Io(_))));
+        assert!(matches!(read_data::<i32>("missing.txt"), Err(AocError::Io(_))));
+    }
+
+    #[cfg(feature = "embedded")]
+    #[test]
+    fn test_read_embedded() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("data").join("day01_input.txt");
+        let expected = std::fs::read_to_string(path).unwrap();
+        assert_eq!(read_embedded("day01_input.txt"), Some(expected.as_str()));
+        assert_eq!(read_embedded("missing.txt"), None);
+        assert!(matches!(read_lines("missing.txt"), Err(AocError::Io(_))));
+    }
+
+    #[test]
+    fn test_read_data_parse_error() {
+        assert!(matches!(read_data::<i32>("day06_example1.txt"), Err(AocError::Parse(_))));
+    }
+
+    #[test]
+    fn test_bbox_from_points() {
+        assert_eq!(BoundingBox2D::from_points(vec![]), None);
+        assert_eq!(BoundingBox2D::from_points(vec![point!(3, -2)]), Some(BoundingBox2D{min: point!(3, -2), max: point!(3, -2)}));
+        let points = vec![point!(1, 5), point!(-3, 2), point!(4, -1), point!(0, 0), point!(2, 7)];
+        assert_eq!(BoundingBox2D::from_points(points), Some(BoundingBox2D{min: point!(-3, -1), max: point!(4, 7)}));
+    }
+
+    #[test]
+    fn test_bbox_iter_rows() {
+        let bbox = BoundingBox2D{min: point!(-1, 4), max: point!(0, 6)};
+        let rows: Vec<Vec<Point2D>> = bbox.iter_rows().map(|row| row.collect()).collect();
+        assert_eq!(rows, vec![
+            vec![point!(-1, 4), point!(0, 4)],
+            vec![point!(-1, 5), point!(0, 5)],
+            vec![point!(-1, 6), point!(0, 6)],
+        ]);
+        assert_eq!(rows.concat(), bbox.iter().collect::<Vec<Point2D>>());
+    }
+
+    #[test]
+    fn test_grid_from_lines() {
+        let lines: Vec<String> = vec!["#..".to_string(), ".@#".to_string(), String::new()];
+        let grid = Grid2D::from_lines(&lines).unwrap();
+        assert_eq!((grid.width, grid.height), (3, 2));
+        assert_eq!(grid.bbox, BoundingBox2D{min: point!(0, 0), max: point!(2, 1)});
+        assert_eq!(grid.get(&point!(1, 1)), Some(&'@'));
+        assert_eq!(grid.get(&point!(2, 0)), Some(&'.'));
+        assert_eq!(grid.get(&point!(3, 0)), None);
+        assert_eq!(grid.get(&point!(0, -1)), None);
+    }
+
+    #[test]
+    fn test_grid_index() {
+        let lines: Vec<String> = ["abc", "def"].iter().map(|row| row.to_string()).collect();
+        let mut grid = Grid2D::from_lines(&lines).unwrap();
+        assert_eq!(grid[point!(0, 0)], 'a');
+        assert_eq!(grid[point!(2, 1)], 'f');
+        grid[point!(1, 1)] = 'E';
+        assert_eq!(grid.get(&point!(1, 1)), Some(&'E'));
+    }
+
+    #[test]
+    #[should_panic(expected = "Vector2D { x: 3, y: 0 } is outside grid bounds Vector2D { x: 0, y: 0 } ..= Vector2D { x: 2, y: 1 }")]
+    fn test_grid_index_out_of_bounds() {
+        let lines: Vec<String> = ["abc", "def"].iter().map(|row| row.to_string()).collect();
+        let grid = Grid2D::from_lines(&lines).unwrap();
+        let _ = grid[point!(3, 0)];
+    }
+
+    #[test]
+    fn test_grid_from_lines_invalid() {
+        let lines: Vec<String> = vec!["#..".to_string(), ".@".to_string()];
+        match Grid2D::from_lines(&lines) {
+            Err(AocError::Parse(msg)) => assert_eq!(msg, "grid row 1 has width 2, expected 3"),
+            other => panic!("expected parse error, got {:?}", other),
+        }
+        assert!(matches!(Grid2D::from_lines(&[String::new()]), Err(AocError::Parse(_))));
+    }
+
+    #[test]
+    fn test_diff_grids() {
+        let before: Vec<String> = ["#.#", "...", "#O#"].iter().map(|row| row.to_string()).collect();
+        let after: Vec<String> = ["#.#", ".O.", "#O#"].iter().map(|row| row.to_string()).collect();
+        assert_eq!(diff_grids(&before, &after), vec![(point!(1, 1), '.', 'O')]);
+        assert_eq!(diff_grids(&after, &after), vec![]);
+        // Missing cells compare as spaces
+        let longer: Vec<String> = ["#.# ", "...#"].iter().map(|row| row.to_string()).collect();
+        assert_eq!(diff_grids(&before, &longer), vec![
+            (point!(3, 1), ' ', '#'),
+            (point!(0, 2), '#', ' '),
+            (point!(1, 2), 'O', ' '),
+            (point!(2, 2), '#', ' '),
+        ]);
+    }
+
+    #[test]
+    fn test_canvas() {
+        let mut canvas: Canvas<u8> = Canvas::new();
+        assert_eq!(canvas.bbox(), None);
+        assert!(canvas.render(|_| '?').is_empty());
+        canvas.set(point!(1, 1), 1);
+        canvas.set(point!(-1, 0), 2);
+        canvas.set(point!(2, 3), 3);
+        canvas.set(point!(1, 1), 4);
+        assert_eq!(canvas.len(), 3);
+        assert_eq!(canvas.get(&point!(1, 1)), Some(&4));
+        assert_eq!(canvas.get(&point!(0, 0)), None);
+        assert_eq!(canvas.bbox(), Some(&BoundingBox2D{min: point!(-1, 0), max: point!(2, 3)}));
+        let rendered = canvas.render(|v| v.map_or('.', |v| (b'0' + v) as char));
+        assert_eq!(rendered, vec!["2...", "..4.", "....", "...3"]);
+    }
+
+    #[test]
+    fn test_flood_fill_connectivity() {
+        // (1, 2) is only connected diagonally, and the right-hand side isn't connected at all
+        let lines: Vec<String> = ["..#..", ".##..", "#.###", "###..", "...#."]
+            .iter().map(|row| row.to_string()).collect();
+        let grid = Grid2D::from_lines(&lines).unwrap();
+        let passable = |p: &Point2D| grid.get(p) == Some(&'.');
+        let four = flood_fill(point!(0, 0), Connectivity::Four, passable);
+        assert_eq!(four.len(), 3);
+        assert_eq!(four.get(&point!(1, 2)), None);
+        let eight = flood_fill(point!(0, 0), Connectivity::Eight, passable);
+        assert_eq!(eight.get(&point!(1, 2)), Some(&2));
+        assert_eq!(eight.get(&point!(3, 0)), None);
+        assert_eq!(eight.len(), 4);
+    }
+
+    #[test]
+    fn test_distance_grid() {
+        let lines: Vec<String> = ["#####", "#..##", "#.#.#", "#...#", "#####"].iter().map(|row| row.to_string()).collect();
+        let maze = Grid2D::from_lines(&lines).unwrap();
+        let grid = distance_grid(point!(1, 1), |p| maze.get(p) == Some(&'.'));
+        assert_eq!(grid.bbox, BoundingBox2D{min: point!(1, 1), max: point!(3, 3)});
+        assert_eq!(grid.data, vec![
+            Some(0), Some(1), None,
+            Some(1), None, Some(5),
+            Some(2), Some(3), Some(4),
+        ]);
+        assert_eq!(grid.get(&point!(3, 2)), Some(&Some(5)));
+        assert_eq!(grid.get(&point!(2, 2)), Some(&None));
+        assert_eq!(grid.get(&point!(0, 0)), None);
+        // Negative positions, around an obstacle at the origin
+        let grid = distance_grid(point!(-1, -1), |p| p.x.abs() <= 1 && p.y.abs() <= 1 && *p != point!(0, 0));
+        assert_eq!((grid.width, grid.height), (3, 3));
+        assert_eq!(grid.get(&point!(-1, -1)), Some(&Some(0)));
+        assert_eq!(grid.get(&point!(0, 0)), Some(&None));
+        assert_eq!(grid.get(&point!(1, 1)), Some(&Some(4)));
+    }
+
+    #[test]
+    fn test_astar_grid() {
+        let lines: Vec<String> = [
+            ".....",
+            ".###.",
+            "...#.",
+            "##.#.",
+            ".....",
+        ].iter().map(|row| row.to_string()).collect();
+        let grid = Grid2D::from_lines(&lines).unwrap();
+        let open = |c: &char| *c == '.';
+        // The wall forces a detour around the top or through the middle
+        let (path, cost) = astar_grid(&grid, point!(0, 2), point!(4, 2), open).unwrap();
+        assert_eq!(cost, 8);
+        assert_eq!(path.len(), cost + 1);
+        assert_eq!((path[0], path[cost]), (point!(0, 2), point!(4, 2)));
+        assert!(path.windows(2).all(|w| w[0].manhattan_distance(&w[1]) == 1));
+        assert!(path.iter().all(|p| grid.get(p) == Some(&'.')));
+        assert_eq!(astar_grid(&grid, point!(1, 1), point!(1, 1), |_| true), Some((vec![point!(1, 1)], 0)));
+        // Goal is a wall, or outside the grid
+        assert_eq!(astar_grid(&grid, point!(0, 0), point!(2, 1), open), None);
+        assert_eq!(astar_grid(&grid, point!(0, 0), point!(5, 0), open), None);
+    }
+
+    #[test]
+    fn test_cycle_detector() {
+        // 0, 1, 2, 3, 4, 5, 6, 2, ...
+        let mut detector = CycleDetector::new(0, |&x: &u32| if x < 6 { x + 1 } else { 2 });
+        assert_eq!(detector.find_cycle(100), Some((2, 5)));
+        assert_eq!(*detector.state(), 2);
+        // x -> x + 3 (mod 10) visits every value before repeating
+        assert_eq!(CycleDetector::new(4, |&x: &u32| (x + 3) % 10).find_cycle(100), Some((0, 10)));
+        assert_eq!(CycleDetector::new(4, |&x: &u32| (x + 3) % 10).find_cycle(9), None);
+        // A fixed point
+        assert_eq!(CycleDetector::new(1, |&x: &u32| x).find_cycle(100), Some((0, 1)));
+    }
+
+    fn to_pixels(rows: &[&str]) -> Vec<String> {
+        rows.iter().map(|row| row.to_string()).collect()
+    }
+
+    #[test]
+    fn test_ocr_letters_day08() {
+        assert_eq!(ocr_letters(&to_pixels(&[
+            "XXXX X  X   XX X  X X    ",
+            "X    X  X    X X  X X    ",
+            "XXX  XXXX    X X  X X    ",
+            "X    X  X    X X  X X    ",
+            "X    X  X X  X X  X X    ",
+            "X    X  X  XX   XX  XXXX ",
+        ])), "FHJUL");
+    }
+
+    #[test]
+    fn test_ocr_letters_day11() {
+        assert_eq!(ocr_letters(&to_pixels(&[
+            " XXXX X    XXXX XXX  X  X   XX XXX   XX    ",
+            "    X X    X    X  X X X     X X  X X  X   ",
+            "   X  X    XXX  XXX  XX      X X  X X  X   ",
+            "  X   X    X    X  X X X     X XXX  XXXX   ",
+            " X    X    X    X  X X X  X  X X X  X  X   ",
+            " XXXX XXXX XXXX XXX  X  X  XX  X  X X  X   ",
+        ])), "ZLEBKJRA");
+    }
+
+    #[test]
+    fn test_encode_ppm() {
+        let data = encode_ppm(&to_pixels(&["X "]), 3);
+        let header = b"P6\n6 3\n255\n";
+        assert_eq!(&data[.. header.len()], header);
+        assert_eq!(data.len(), header.len() + 6 * 3 * 3);
+        // First row: 3 white pixels then 3 black pixels
+        assert_eq!(&data[header.len() .. header.len() + 18], &[
+            255, 255, 255, 255, 255, 255, 255, 255, 255,
+            0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ]);
+    }
+
+    #[test]
+    fn test_write_ppm() {
+        let path = std::env::temp_dir().join("aoc2019_test_write_ppm.ppm");
+        let path = path.to_str().unwrap();
+        write_ppm(path, &to_pixels(&["X "]), 1).unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), b"P6\n2 1\n255\n\xff\xff\xff\x00\x00\x00".to_vec());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_write_ppm_error() {
+        let path = std::env::temp_dir().join("aoc2019_missing_dir").join("test.ppm");
+        assert!(write_ppm(path, &to_pixels(&["X "]), 1).is_err());
+    }
+
+    #[test]
+    fn test_ocr_letters_unknown() {
+        assert_eq!(ocr_letters(&to_pixels(&["X", "X", "X", "X", "X", "X"])), "?");
+        assert_eq!(ocr_letters(&to_pixels(&["", "", "", "", "", ""])), "");
     }
 }
@@ -0,0 +1,20 @@
+//! With the `embedded` feature, the binary doesn't need `data/` in its working directory
+#![cfg(feature = "embedded")]
+
+use std::env;
+use std::process::Command;
+
+use advent_of_code_2019::day01;
+
+#[test]
+fn test_run_outside_data_dir() {
+    let output = Command::new(env!("CARGO_BIN_EXE_advent_of_code_2019"))
+        .args(&["1", "1"])
+        .current_dir(env::temp_dir())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let expected = format!("day01::part1(): {} (", day01::part1().unwrap());
+    assert!(stdout.starts_with(&expected), "{:?}", stdout);
+}